@@ -0,0 +1,23 @@
+//! 构建脚本
+//!
+//! 在可用时把当前 git 提交写入 `KIRO_GIT_COMMIT` 环境变量，供 `/version` 端点使用
+
+use std::process::Command;
+
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty());
+
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=KIRO_GIT_COMMIT={}", commit);
+    }
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
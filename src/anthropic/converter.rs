@@ -58,6 +58,9 @@ impl std::fmt::Display for ConversionError {
 impl std::error::Error for ConversionError {}
 
 /// 将 Anthropic 请求转换为 Kiro 请求
+///
+/// - `system` 会作为历史中的第一组 user/assistant 消息注入对话上下文
+/// - `max_tokens` 在 Kiro API 中没有对应字段，会被丢弃（输出长度由上游决定）
 pub fn convert_request(req: &MessagesRequest) -> Result<ConversionResult, ConversionError> {
     // 1. 映射模型
     let model_id = map_model(&req.model)
@@ -516,6 +519,55 @@ mod tests {
         assert_eq!(determine_chat_trigger_type(&req), "MANUAL");
     }
 
+    #[test]
+    fn test_convert_request_maps_string_system() {
+        let req: MessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 2048,
+            "system": "You are a pirate.",
+            "messages": [{"role": "user", "content": "Hello"}]
+        }))
+        .unwrap();
+        assert_eq!(req.max_tokens, 2048);
+
+        let result = convert_request(&req).unwrap();
+        let kiro_request = crate::kiro::model::requests::kiro::KiroRequest {
+            conversation_state: result.conversation_state,
+            profile_arn: None,
+        };
+        let json = serde_json::to_value(&kiro_request).unwrap();
+
+        let history = json["conversationState"]["history"].as_array().unwrap();
+        assert_eq!(
+            history[0]["userInputMessage"]["content"],
+            "You are a pirate."
+        );
+        assert_eq!(
+            json["conversationState"]["currentMessage"]["userInputMessage"]["content"],
+            "Hello"
+        );
+    }
+
+    #[test]
+    fn test_convert_request_maps_block_system() {
+        let req: MessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "system": [
+                {"type": "text", "text": "Rule one."},
+                {"type": "text", "text": "Rule two.", "cache_control": {"type": "ephemeral"}}
+            ],
+            "messages": [{"role": "user", "content": "Hello"}]
+        }))
+        .unwrap();
+
+        let result = convert_request(&req).unwrap();
+        let Message::User(first) = &result.conversation_state.history[0] else {
+            panic!("system prompt should be injected as a user message");
+        };
+        assert_eq!(first.user_input_message.content, "Rule one.\nRule two.");
+    }
+
     #[test]
     fn test_is_unsupported_tool() {
         assert!(is_unsupported_tool("web_search"));
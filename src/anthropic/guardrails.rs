@@ -0,0 +1,163 @@
+//! 请求内容策略
+//!
+//! 在转发 `/v1/messages` 请求前统一注入系统提示词，并拒绝用户消息中包含屏蔽词的请求
+
+use serde_json::{Value, json};
+
+/// 请求内容策略
+#[derive(Debug, Clone, Default)]
+pub struct Guardrails {
+    /// 注入到系统提示词最前面的内容
+    prepend_system_prompt: Option<String>,
+    /// 屏蔽词（已转为小写，匹配时不区分大小写）
+    blocked_patterns: Vec<String>,
+}
+
+/// 请求命中屏蔽词
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedContent {
+    /// 命中的屏蔽词（原始配置值的小写形式）
+    pub pattern: String,
+}
+
+impl Guardrails {
+    pub fn new(prepend_system_prompt: Option<String>, blocked_patterns: &[String]) -> Self {
+        Self {
+            prepend_system_prompt,
+            blocked_patterns: blocked_patterns.iter().map(|p| p.to_lowercase()).collect(),
+        }
+    }
+
+    /// 对请求体应用策略：先检查屏蔽词，再注入系统提示词
+    pub fn apply(&self, body: &mut Value) -> Result<(), BlockedContent> {
+        if let Some(pattern) = self.find_blocked_pattern(body) {
+            return Err(BlockedContent { pattern });
+        }
+        if let Some(prompt) = &self.prepend_system_prompt {
+            prepend_system(body, prompt);
+        }
+        Ok(())
+    }
+
+    /// 在用户消息的文本内容中查找屏蔽词
+    fn find_blocked_pattern(&self, body: &Value) -> Option<String> {
+        if self.blocked_patterns.is_empty() {
+            return None;
+        }
+        let messages = body.get("messages")?.as_array()?;
+        messages
+            .iter()
+            .filter(|m| m.get("role").and_then(|r| r.as_str()) == Some("user"))
+            .filter_map(|m| m.get("content"))
+            .flat_map(user_texts)
+            .find_map(|text| {
+                let text = text.to_lowercase();
+                self.blocked_patterns
+                    .iter()
+                    .find(|pattern| text.contains(pattern.as_str()))
+                    .cloned()
+            })
+    }
+}
+
+/// 提取消息内容中的文本：字符串内容、`text` 类型的内容块，
+/// 以及 `tool_result` 内容块中的内容（字符串或嵌套的内容块）
+fn user_texts(content: &Value) -> Vec<&str> {
+    match content {
+        Value::String(text) => vec![text.as_str()],
+        Value::Array(blocks) => blocks
+            .iter()
+            .flat_map(|b| match b.get("type").and_then(|t| t.as_str()) {
+                Some("text") => b.get("text").and_then(|t| t.as_str()).into_iter().collect(),
+                Some("tool_result") => b.get("content").map(user_texts).unwrap_or_default(),
+                _ => Vec::new(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 将提示词放到 `system` 最前面，兼容字符串和内容块数组两种形式
+fn prepend_system(body: &mut Value, prompt: &str) {
+    let Some(request) = body.as_object_mut() else {
+        return;
+    };
+    match request.get_mut("system") {
+        Some(Value::String(system)) => *system = format!("{}\n\n{}", prompt, system),
+        Some(Value::Array(blocks)) => blocks.insert(0, json!({"type": "text", "text": prompt})),
+        _ => {
+            request.insert("system".to_string(), Value::String(prompt.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guardrails() -> Guardrails {
+        Guardrails::new(
+            Some("Follow the company policy.".to_string()),
+            &["Secret Project".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_prepend_system_prompt() {
+        let guardrails = guardrails();
+
+        let mut body = json!({"messages": [{"role": "user", "content": "Hi"}]});
+        guardrails.apply(&mut body).unwrap();
+        assert_eq!(body["system"], "Follow the company policy.");
+
+        let mut body = json!({"system": "Be brief.", "messages": []});
+        guardrails.apply(&mut body).unwrap();
+        assert_eq!(body["system"], "Follow the company policy.\n\nBe brief.");
+
+        let mut body = json!({"system": [{"type": "text", "text": "Be brief."}], "messages": []});
+        guardrails.apply(&mut body).unwrap();
+        assert_eq!(body["system"][0]["text"], "Follow the company policy.");
+        assert_eq!(body["system"][1]["text"], "Be brief.");
+    }
+
+    #[test]
+    fn test_blocked_pattern_rejects_user_content() {
+        let guardrails = guardrails();
+
+        let mut body = json!({"messages": [
+            {"role": "user", "content": [{"type": "text", "text": "Tell me about the SECRET project"}]}
+        ]});
+        let err = guardrails.apply(&mut body).unwrap_err();
+        assert_eq!(err.pattern, "secret project");
+        // 被拒绝的请求不注入提示词
+        assert!(body.get("system").is_none());
+
+        // 只检查用户消息
+        let mut body = json!({"messages": [
+            {"role": "assistant", "content": "The secret project is cancelled."},
+            {"role": "user", "content": "OK"}
+        ]});
+        assert!(guardrails.apply(&mut body).is_ok());
+    }
+
+    #[test]
+    fn test_blocked_pattern_in_tool_result() {
+        let guardrails = guardrails();
+
+        let mut body = json!({"messages": [
+            {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "toolu_1", "content": "secret project notes"}
+            ]}
+        ]});
+        assert_eq!(guardrails.apply(&mut body).unwrap_err().pattern, "secret project");
+
+        let mut body = json!({"messages": [
+            {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "toolu_1", "content": [
+                    {"type": "text", "text": "Contents of the Secret Project folder"}
+                ]}
+            ]}
+        ]});
+        assert_eq!(guardrails.apply(&mut body).unwrap_err().pattern, "secret project");
+    }
+}
@@ -4,28 +4,35 @@ use std::convert::Infallible;
 
 use axum::{
     body::Body,
-    extract::State,
-    http::{header, StatusCode},
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Json, Response},
     Json as JsonExtractor,
 };
 use bytes::Bytes;
-use futures::{stream, Stream, StreamExt};
+use futures::{future::BoxFuture, stream, Stream, StreamExt};
 use serde_json::json;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, TryAcquireError};
 use tokio::time::interval;
 use uuid::Uuid;
 use crate::token;
 use crate::kiro::model::events::Event;
 use crate::kiro::model::requests::kiro::KiroRequest;
 use crate::kiro::parser::decoder::EventStreamDecoder;
+use crate::kiro::provider::UpstreamStatusError;
 
-use super::converter::{convert_request, ConversionError};
+use super::converter::{convert_request, map_model, ConversionError};
+use super::i18n::{ApiMessage, Locale};
+use super::latency::{LatencyTimer, LatencyTracker};
 use super::middleware::AppState;
-use super::stream::{SseEvent, StreamContext};
+use super::response_cache::{cache_key, CachedResponse, ResponseCache};
+use super::stream::{SseEvent, StreamContext, StreamFormat};
 use super::types::{
-    CountTokensRequest, CountTokensResponse, ErrorResponse, MessagesRequest, Model, ModelsResponse,
+    CountTokensRequest, CountTokensResponse, DebugDecodeRequest, ErrorResponse,
+    MaintenanceRequest, MessagesQuery, MessagesRequest, Model, ModelsResponse, VersionResponse,
 };
+use super::validation::validate_messages_request;
 
 /// GET /v1/models
 ///
@@ -69,13 +76,235 @@ pub async fn get_models() -> impl IntoResponse {
     })
 }
 
+/// GET /healthz
+///
+/// 健康检查，附带最近一次时钟偏差检测结果
+pub async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
+    let clock_skew = state
+        .clock_skew_monitor
+        .as_ref()
+        .and_then(|m| m.last_sample());
+
+    Json(json!({
+        "status": "ok",
+        "clockSkew": clock_skew
+    }))
+}
+
+/// GET /version
+///
+/// 返回网关版本、构建提交和配置的 Kiro 版本（无需认证）
+pub async fn get_version(State(state): State<AppState>) -> impl IntoResponse {
+    Json(version_info(&state))
+}
+
+fn version_info(state: &AppState) -> VersionResponse {
+    VersionResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: option_env!("KIRO_GIT_COMMIT"),
+        kiro_version: state.kiro_version.clone(),
+    }
+}
+
+/// GET /stats/latency
+///
+/// 返回按模型、流式/非流式分组的滚动上游延迟百分位（毫秒）
+pub async fn get_latency_stats(State(state): State<AppState>) -> impl IntoResponse {
+    Json(json!({
+        "data": state.latency_tracker.snapshot()
+    }))
+}
+
+/// GET /stats/cache
+///
+/// 返回响应缓存的命中统计（未启用缓存时为 null）
+pub async fn get_cache_stats(State(state): State<AppState>) -> impl IntoResponse {
+    Json(json!({
+        "responseCache": state.response_cache.as_ref().map(|c| c.stats())
+    }))
+}
+
+/// GET /stats/throttling
+///
+/// 返回滚动窗口内上游 429 响应的比例（未配置 Provider 时为 null）
+pub async fn get_throttling_stats(State(state): State<AppState>) -> impl IntoResponse {
+    Json(json!({
+        "upstream": state.throttle_tracker.as_ref().map(|t| t.stats())
+    }))
+}
+
+/// GET /admin/maintenance
+///
+/// 返回维护模式的当前状态
+pub async fn get_maintenance(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.maintenance.status())
+}
+
+/// POST /admin/maintenance
+///
+/// 开启或关闭维护模式；开启期间 `/v1` 请求返回 503 并带上提示消息
+pub async fn set_maintenance(
+    State(state): State<AppState>,
+    JsonExtractor(payload): JsonExtractor<MaintenanceRequest>,
+) -> impl IntoResponse {
+    state.maintenance.set(payload.enabled, payload.message);
+    let status = state.maintenance.status();
+    if status.enabled {
+        tracing::warn!("维护模式已开启: {}", status.message);
+    } else {
+        tracing::info!("维护模式已关闭");
+    }
+    Json(status)
+}
+
+/// GET /admin/machine-id
+///
+/// 返回上游请求当前使用的 machine_id（刷新 Token 后随凭证更新），
+/// 便于确认编辑凭证或轮换 refreshToken 后设备指纹是否变化
+pub async fn get_machine_id(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if state.kiro_provider.is_none() {
+        let locale = Locale::from_headers(&headers, state.default_locale);
+        return provider_not_configured_response(locale);
+    }
+
+    let machine_id = state.machine_id.read().unwrap().clone();
+    Json(json!({ "machineId": machine_id })).into_response()
+}
+
+/// POST /debug/decode
+///
+/// 用事件流解码器解析 base64 编码的原始 Kiro 响应字节，返回每一帧及其解析结果，
+/// 用于在没有上游连接的情况下排查响应格式变化
+pub async fn debug_decode(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    JsonExtractor(request): JsonExtractor<DebugDecodeRequest>,
+) -> Response {
+    use base64::Engine;
+
+    let bytes = match base64::engine::general_purpose::STANDARD.decode(request.data.trim()) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let locale = Locale::from_headers(&headers, state.default_locale);
+            return invalid_request_response(ApiMessage::InvalidBase64(e.to_string()).text(locale));
+        }
+    };
+    Json(decode_event_stream(&bytes)).into_response()
+}
+
+/// 解码事件流，返回帧列表、解码错误以及跳过/残留的字节数
+fn decode_event_stream(bytes: &[u8]) -> serde_json::Value {
+    let mut decoder = EventStreamDecoder::new();
+    let mut errors = Vec::new();
+    if let Err(e) = decoder.feed(bytes) {
+        errors.push(e.to_string());
+    }
+
+    let mut frames = Vec::new();
+    for result in decoder.decode_iter() {
+        let frame = match result {
+            Ok(frame) => frame,
+            Err(e) => {
+                errors.push(e.to_string());
+                continue;
+            }
+        };
+        let payload = serde_json::from_slice::<serde_json::Value>(&frame.payload)
+            .unwrap_or_else(|_| serde_json::Value::String(frame.payload_as_str()));
+        let mut entry = json!({
+            "messageType": frame.message_type(),
+            "eventType": frame.event_type(),
+            "payload": payload,
+        });
+        match Event::from_frame(frame) {
+            Ok(event) => entry["event"] = json!(event_kind(&event)),
+            Err(e) => entry["error"] = json!(e.to_string()),
+        }
+        frames.push(entry);
+    }
+
+    json!({
+        "frames": frames,
+        "errors": errors,
+        "bytesSkipped": decoder.bytes_skipped(),
+        "trailingBytes": decoder.buffer_len(),
+    })
+}
+
+/// 事件类型名称
+fn event_kind(event: &Event) -> &'static str {
+    match event {
+        Event::AssistantResponse(_) => "assistantResponse",
+        Event::ToolUse(_) => "toolUse",
+        Event::Metering(_) => "metering",
+        Event::ContextUsage(_) => "contextUsage",
+        Event::Unknown {} => "unknown",
+        Event::Error { .. } => "error",
+        Event::Exception { .. } => "exception",
+    }
+}
+
 /// POST /v1/messages
 ///
 /// 创建消息（对话）
+///
+/// 流式请求默认输出 SSE；`?format=ndjson` 或 `Accept: application/x-ndjson` 时输出 NDJSON
 pub async fn post_messages(
     State(state): State<AppState>,
-    JsonExtractor(payload): JsonExtractor<MessagesRequest>,
+    Query(query): Query<MessagesQuery>,
+    headers: HeaderMap,
+    JsonExtractor(mut body): JsonExtractor<serde_json::Value>,
 ) -> Response {
+    let locale = Locale::from_headers(&headers, state.default_locale);
+
+    // 先补充模型默认参数，使默认值同样经过校验并参与缓存键计算
+    apply_model_defaults(&mut body, &state.model_defaults);
+
+    // 在任何上游工作之前先校验请求体，返回精确到字段的错误
+    if let Err(e) = validate_messages_request(&body) {
+        tracing::warn!("请求校验失败: {}", e);
+        return invalid_request_response(e.text(locale));
+    }
+
+    // 内容策略：拒绝包含屏蔽词的请求，并注入系统提示词（注入后的请求体参与缓存键计算）
+    if let Err(blocked) = state.guardrails.apply(&mut body) {
+        tracing::warn!("请求命中屏蔽词: {}", blocked.pattern);
+        return invalid_request_response(ApiMessage::ContentBlocked.text(locale));
+    }
+
+    let request_timeout = match request_timeout_override(&headers, state.max_request_timeout) {
+        Ok(timeout) => timeout,
+        Err(message) => return invalid_request_response(message.text(locale)),
+    };
+    let stream_format = match negotiate_stream_format(&headers, query.format.as_deref()) {
+        Ok(format) => format,
+        Err(message) => return invalid_request_response(message.text(locale)),
+    };
+
+    // 确定性请求优先查询响应缓存，命中时不调用上游
+    let cache = match &state.response_cache {
+        Some(cache) => cache_key(&body).map(|key| (cache.clone(), key)),
+        None => None,
+    };
+    if let Some((cache, key)) = &cache
+        && let Some(cached) = cache.get(key)
+    {
+        tracing::info!("响应缓存命中");
+        let requested_model = body.get("model").and_then(|m| m.as_str()).unwrap_or_default();
+        let mut response =
+            (StatusCode::OK, [("x-kiro-cache", "hit")], Json(cached.body)).into_response();
+        mark_served_model(&mut response, requested_model, &cached.served_model);
+        return response;
+    }
+
+    let mut payload: MessagesRequest = match serde_json::from_value(body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::warn!("请求解析失败: {}", e);
+            return invalid_request_response(ApiMessage::InvalidRequestBody(e.to_string()).text(locale));
+        }
+    };
+
     tracing::info!(
         model = %payload.model,
         max_tokens = %payload.max_tokens,
@@ -88,60 +317,43 @@ pub async fn post_messages(
         Some(p) => p.clone(),
         None => {
             tracing::error!("KiroProvider 未配置");
-            return (
-                StatusCode::SERVICE_UNAVAILABLE,
-                Json(ErrorResponse::new(
-                    "service_unavailable",
-                    "Kiro API provider not configured",
-                )),
-            )
-                .into_response();
+            return provider_not_configured_response(locale);
         }
     };
 
-    // 转换请求
-    let conversion_result = match convert_request(&payload) {
-        Ok(result) => result,
-        Err(e) => {
-            let (error_type, message) = match &e {
-                ConversionError::UnsupportedModel(model) => {
-                    ("invalid_request_error", format!("模型不支持: {}", model))
-                }
-                ConversionError::EmptyMessages => {
-                    ("invalid_request_error", "消息列表为空".to_string())
-                }
-            };
-            tracing::warn!("请求转换失败: {}", e);
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(ErrorResponse::new(error_type, message)),
-            )
-                .into_response();
+    // 流式请求需要先获取并发许可，许可随响应流一起释放
+    let stream_permit = if payload.stream {
+        match try_acquire_stream_permit(&state) {
+            Ok(permit) => permit,
+            Err(_) => {
+                tracing::warn!("流式连接数已达上限，拒绝新的流式请求");
+                return stream_limit_exceeded_response(locale);
+            }
         }
+    } else {
+        None
     };
 
-    // 构建 Kiro 请求
-    let kiro_request = KiroRequest {
-        conversation_state: conversion_result.conversation_state,
-        profile_arn: state.profile_arn.clone(),
-    };
-
-    let request_body = match serde_json::to_string(&kiro_request) {
-        Ok(body) => body,
-        Err(e) => {
-            tracing::error!("序列化请求失败: {}", e);
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse::new(
-                    "internal_error",
-                    format!("序列化请求失败: {}", e),
-                )),
-            )
-                .into_response();
+    // 按模型回退链依次构建 Kiro 请求：请求的模型在前，配置的备选模型在后
+    let requested_model = payload.model.clone();
+    let mut candidates = Vec::new();
+    for (i, model) in state.model_chain(&requested_model).into_iter().enumerate() {
+        payload.model = model.clone();
+        match build_kiro_request_body(&payload, state.profile_arn.clone()) {
+            Ok(request_body) => {
+                tracing::debug!("Kiro request body ({}): {}", model, request_body);
+                candidates.push(ModelCandidate {
+                    model,
+                    request_body,
+                });
+            }
+            Err(e) if i > 0 => {
+                tracing::warn!("备选模型 {} 无法使用，已跳过: {}", model, e);
+            }
+            Err(e) => return e.into_localized_response(locale),
         }
-    };
-
-    tracing::debug!("Kiro request body: {}", request_body);
+    }
+    payload.model = requested_model;
 
     // 估算输入 tokens
     let input_tokens = token::count_all_tokens(payload.model.clone(), payload.system, payload.messages, payload.tools) as i32;
@@ -152,67 +364,449 @@ pub async fn post_messages(
         .map(|t| t.thinking_type == "enabled")
         .unwrap_or(false);
 
+    let latency_tracker = state.latency_tracker.clone();
+    let timeouts = UpstreamTimeouts {
+        queue: state.queue_timeout,
+        request: request_timeout,
+    };
+
     if payload.stream {
         // 流式响应
-        handle_stream_request(provider, &request_body, &payload.model, input_tokens, thinking_enabled).await
+        let options = StreamOptions {
+            thinking_enabled,
+            format: stream_format,
+            locale,
+        };
+        handle_stream_request(provider, timeouts, &candidates, input_tokens, options, stream_permit, latency_tracker).await
     } else {
         // 非流式响应
-        handle_non_stream_request(provider, &request_body, &payload.model, input_tokens).await
+        handle_non_stream_request(provider, timeouts, &candidates, input_tokens, latency_tracker, cache, locale).await
+    }
+}
+
+/// 按请求的模型补充默认参数
+///
+/// 只填充请求中缺失的顶层字段，客户端显式提供的字段（包括 `null`）保持不变
+fn apply_model_defaults(
+    body: &mut serde_json::Value,
+    model_defaults: &std::collections::HashMap<String, serde_json::Value>,
+) {
+    let Some(model) = body.get("model").and_then(|m| m.as_str()) else {
+        return;
+    };
+    let Some(serde_json::Value::Object(defaults)) = model_defaults.get(model) else {
+        return;
+    };
+    let Some(request) = body.as_object_mut() else {
+        return;
+    };
+    for (key, value) in defaults {
+        request.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+/// 客户端指定单次请求超时的请求头
+const TIMEOUT_OVERRIDE_HEADER: &str = "x-kiro-timeout-secs";
+
+/// 调用上游时使用的超时设置
+#[derive(Debug, Clone, Copy)]
+struct UpstreamTimeouts {
+    /// 等待上游空闲的排队超时
+    queue: Option<Duration>,
+    /// 单次请求超时（来自 `x-kiro-timeout-secs`，未指定时使用客户端默认超时）
+    request: Option<Duration>,
+}
+
+/// 解析 `x-kiro-timeout-secs` 请求头，超过上限时截断为上限
+fn request_timeout_override(
+    headers: &HeaderMap,
+    max: Duration,
+) -> Result<Option<Duration>, ApiMessage> {
+    let Some(value) = headers.get(TIMEOUT_OVERRIDE_HEADER) else {
+        return Ok(None);
+    };
+    let secs = value
+        .to_str()
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .ok_or(ApiMessage::InvalidTimeoutOverride(TIMEOUT_OVERRIDE_HEADER))?;
+    Ok(Some(Duration::from_secs(secs).min(max)))
+}
+
+/// 按 `format` 查询参数或 `Accept` 头确定流式输出格式，查询参数优先
+fn negotiate_stream_format(
+    headers: &HeaderMap,
+    format: Option<&str>,
+) -> Result<StreamFormat, ApiMessage> {
+    match format {
+        Some("sse") => return Ok(StreamFormat::Sse),
+        Some("ndjson") => return Ok(StreamFormat::Ndjson),
+        Some(_) => return Err(ApiMessage::InvalidStreamFormat),
+        None => {}
+    }
+    let wants_ndjson = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.split(';').next().unwrap_or("").trim() == "application/x-ndjson");
+    Ok(if wants_ndjson {
+        StreamFormat::Ndjson
+    } else {
+        StreamFormat::Sse
+    })
+}
+
+/// 流式响应的输出选项
+#[derive(Debug, Clone, Copy)]
+struct StreamOptions {
+    thinking_enabled: bool,
+    format: StreamFormat,
+    /// 错误信息语言
+    locale: Locale,
+}
+
+/// 模型回退链中的一个候选：模型名及对应的 Kiro 请求体
+struct ModelCandidate {
+    model: String,
+    request_body: String,
+}
+
+/// 构建 Kiro 请求体失败
+#[derive(Debug)]
+enum BuildRequestError {
+    Conversion(ConversionError),
+    Serialize(serde_json::Error),
+}
+
+impl std::fmt::Display for BuildRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildRequestError::Conversion(e) => write!(f, "{}", e),
+            BuildRequestError::Serialize(e) => write!(f, "序列化请求失败: {}", e),
+        }
+    }
+}
+
+impl BuildRequestError {
+    /// 按语言构建错误响应
+    fn into_localized_response(self, locale: Locale) -> Response {
+        match self {
+            BuildRequestError::Conversion(e) => {
+                let message = match &e {
+                    ConversionError::UnsupportedModel(model) => {
+                        ApiMessage::UnsupportedModel(model.clone())
+                    }
+                    ConversionError::EmptyMessages => ApiMessage::EmptyMessages,
+                };
+                tracing::warn!("请求转换失败: {}", e);
+                invalid_request_response(message.text(locale))
+            }
+            BuildRequestError::Serialize(e) => {
+                tracing::error!("序列化请求失败: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse::new(
+                        "internal_error",
+                        ApiMessage::SerializeFailed(e.to_string()).text(locale),
+                    )),
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+/// 转换 Anthropic 请求并序列化为 Kiro 请求体
+fn build_kiro_request_body(
+    payload: &MessagesRequest,
+    profile_arn: Option<String>,
+) -> Result<String, BuildRequestError> {
+    let conversion_result = convert_request(payload).map_err(BuildRequestError::Conversion)?;
+
+    let kiro_request = KiroRequest {
+        conversation_state: conversion_result.conversation_state,
+        profile_arn,
+    };
+
+    serde_json::to_string(&kiro_request).map_err(BuildRequestError::Serialize)
+}
+
+/// 模型替换时返回实际使用的模型的响应头
+const SERVED_MODEL_HEADER: &str = "x-kiro-served-model";
+
+/// 上游表示模型不可用的错误原因（响应体中的 `reason` 字段）
+const MODEL_REJECTION_REASONS: &[&str] = &["INVALID_MODEL_ID", "MODEL_TEMPORARILY_UNAVAILABLE"];
+
+/// 判断上游错误是否为针对模型的拒绝，此类错误可尝试备选模型
+///
+/// 要求状态码为 400/404，且响应体表明是模型不可用（`reason` 为模型相关的原因，
+/// 或没有 `reason` 时 `message` 提示模型无效）。请求本身格式错误等其他 400 不回退
+fn is_model_rejection(error: &anyhow::Error) -> bool {
+    let Some(e) = error.downcast_ref::<UpstreamStatusError>() else {
+        return false;
+    };
+    if e.status != reqwest::StatusCode::BAD_REQUEST && e.status != reqwest::StatusCode::NOT_FOUND {
+        return false;
+    }
+    let Ok(body) = serde_json::from_str::<serde_json::Value>(&e.body) else {
+        return false;
+    };
+    match body.get("reason").and_then(|r| r.as_str()) {
+        Some(reason) => MODEL_REJECTION_REASONS.contains(&reason),
+        None => body
+            .get("message")
+            .and_then(|m| m.as_str())
+            .is_some_and(|m| m.to_ascii_lowercase().contains("invalid model")),
     }
 }
 
+/// 按回退链依次尝试各候选模型
+///
+/// 只有上游对模型的拒绝（见 [`is_model_rejection`]）会切换到下一个候选，其他错误直接返回；
+/// 所有候选共用同一个重试截止时间，超过后不再尝试后续候选。
+/// 成功时返回结果和实际使用的模型名
+async fn call_with_model_fallback<S, T, F>(
+    candidates: &[ModelCandidate],
+    deadline: Option<Instant>,
+    target: &mut S,
+    mut attempt: F,
+) -> anyhow::Result<(T, String)>
+where
+    F: for<'a> FnMut(&'a mut S, &'a str) -> BoxFuture<'a, anyhow::Result<T>>,
+{
+    let mut last_error = None;
+    for candidate in candidates {
+        if let Some(e) = &last_error
+            && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            tracing::warn!("重试时间预算已用尽，不再尝试备选模型 {}: {}", candidate.model, e);
+            break;
+        }
+        match attempt(target, &candidate.request_body).await {
+            Ok(value) => return Ok((value, candidate.model.clone())),
+            Err(e) if is_model_rejection(&e) => {
+                tracing::warn!("模型 {} 被上游拒绝，尝试下一个备选模型: {}", candidate.model, e);
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("没有可用的模型")))
+}
+
+/// 实际使用的模型与请求的模型不同时，在响应头中标明
+fn mark_served_model(response: &mut Response, requested_model: &str, served_model: &str) {
+    if requested_model != served_model
+        && let Ok(value) = header::HeaderValue::from_str(served_model)
+    {
+        response.headers_mut().insert(SERVED_MODEL_HEADER, value);
+    }
+}
+
+/// 延迟统计的分组模型名：按映射后的 Kiro 模型分组，避免客户端任意的模型名使分组数无限增长
+fn latency_model(model: &str) -> String {
+    map_model(model).unwrap_or_else(|| model.to_string())
+}
+
+/// 构建 400 invalid_request_error 响应
+fn invalid_request_response(message: impl Into<String>) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse::new("invalid_request_error", message)),
+    )
+        .into_response()
+}
+
+/// 未配置 KiroProvider 时的 503 响应
+fn provider_not_configured_response(locale: Locale) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ErrorResponse::new(
+            "service_unavailable",
+            ApiMessage::ProviderNotConfigured.text(locale),
+        )),
+    )
+        .into_response()
+}
+
+/// 上游调用或读取失败时的 502 响应
+fn upstream_error_response(message: ApiMessage, locale: Locale) -> Response {
+    (
+        StatusCode::BAD_GATEWAY,
+        Json(ErrorResponse::new("api_error", message.text(locale))),
+    )
+        .into_response()
+}
+
+/// 排队超时后建议客户端重试的间隔（秒）
+const QUEUE_RETRY_AFTER_SECS: u64 = 1;
+
+/// 等待获取锁，配置了排队超时时最多等待该时长
+async fn lock_with_timeout<T>(
+    mutex: &tokio::sync::Mutex<T>,
+    timeout: Option<Duration>,
+) -> Result<tokio::sync::MutexGuard<'_, T>, tokio::time::error::Elapsed> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, mutex.lock()).await,
+        None => Ok(mutex.lock().await),
+    }
+}
+
+/// 排队超时的 503 响应（带 Retry-After）
+fn queue_timeout_response(locale: Locale) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, QUEUE_RETRY_AFTER_SECS.to_string())],
+        Json(ErrorResponse::new(
+            "overloaded_error",
+            ApiMessage::QueueTimeout.text(locale),
+        )),
+    )
+        .into_response()
+}
+
+/// 流式连接已满时建议客户端重试的间隔（秒）
+const STREAM_RETRY_AFTER_SECS: u64 = 5;
+
+/// 尝试获取流式连接许可
+///
+/// 未配置并发上限时返回 `Ok(None)`
+fn try_acquire_stream_permit(
+    state: &AppState,
+) -> Result<Option<OwnedSemaphorePermit>, TryAcquireError> {
+    match &state.stream_semaphore {
+        Some(semaphore) => semaphore.clone().try_acquire_owned().map(Some),
+        None => Ok(None),
+    }
+}
+
+/// 流式连接数已达上限时的 503 响应（带 Retry-After）
+fn stream_limit_exceeded_response(locale: Locale) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, STREAM_RETRY_AFTER_SECS.to_string())],
+        Json(ErrorResponse::new(
+            "overloaded_error",
+            ApiMessage::TooManyStreams.text(locale),
+        )),
+    )
+        .into_response()
+}
+
 /// 处理流式请求
 async fn handle_stream_request(
     provider: std::sync::Arc<tokio::sync::Mutex<crate::kiro::provider::KiroProvider>>,
-    request_body: &str,
-    model: &str,
+    timeouts: UpstreamTimeouts,
+    candidates: &[ModelCandidate],
     input_tokens: i32,
-    thinking_enabled: bool,
+    options: StreamOptions,
+    stream_permit: Option<OwnedSemaphorePermit>,
+    latency_tracker: std::sync::Arc<LatencyTracker>,
 ) -> Response {
+    let locale = options.locale;
     // 调用 Kiro API
-    let response = {
-        let mut provider_guard = provider.lock().await;
-        match provider_guard.call_api_stream(request_body).await {
-            Ok(resp) => resp,
+    let (response, served_model, timer) = {
+        let Ok(mut provider_guard) = lock_with_timeout(&provider, timeouts.queue).await else {
+            tracing::warn!("等待上游空闲超时，拒绝请求");
+            return queue_timeout_response(locale);
+        };
+        // 重试时间预算和延迟统计都从获取上游后开始计算，排队等待不计入
+        let deadline = provider_guard.retry_deadline();
+        let mut timer = latency_tracker.start(true);
+        match call_with_model_fallback(candidates, deadline, &mut *provider_guard, |p, body| {
+            Box::pin(p.call_api_stream(body, timeouts.request, deadline))
+        })
+        .await
+        {
+            Ok((response, served_model)) => {
+                timer.mark_first_byte(&latency_model(&served_model));
+                (response, served_model, timer)
+            }
             Err(e) => {
                 tracing::error!("Kiro API 调用失败: {}", e);
-                return (
-                    StatusCode::BAD_GATEWAY,
-                    Json(ErrorResponse::new(
-                        "api_error",
-                        format!("上游 API 调用失败: {}", e),
-                    )),
-                )
-                    .into_response();
+                return upstream_error_response(ApiMessage::UpstreamFailed(e.to_string()), locale);
             }
         }
     };
+    let model = served_model.as_str();
 
     // 创建流处理上下文
-    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, thinking_enabled);
+    let mut ctx = StreamContext::new_with_thinking(model, input_tokens, options.thinking_enabled);
 
     // 生成初始事件
     let initial_events = ctx.generate_initial_events();
 
     // 创建 SSE 流
-    let stream = create_sse_stream(response, ctx, initial_events);
+    let stream = guarded_stream(response, ctx, initial_events, options.format, stream_permit, timer);
 
-    // 返回 SSE 响应
-    Response::builder()
+    // 返回 SSE / NDJSON 响应
+    let mut response = Response::builder()
         .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CONTENT_TYPE, options.format.content_type())
         .header(header::CACHE_CONTROL, "no-cache")
         .header(header::CONNECTION, "keep-alive")
         .body(Body::from_stream(stream))
-        .unwrap()
+        .unwrap();
+    mark_served_model(&mut response, &candidates[0].model, model);
+    response
+}
+
+/// 创建与 [`StreamGuard`] 绑定的 SSE 事件流
+///
+/// 客户端断开时 axum 会丢弃响应流，上游 bytes_stream 随之被丢弃，连接立即中止；
+/// 守卫随流一起释放并发许可，只有正常结束时才记录延迟
+fn guarded_stream(
+    response: reqwest::Response,
+    ctx: StreamContext,
+    initial_events: Vec<SseEvent>,
+    format: StreamFormat,
+    permit: Option<OwnedSemaphorePermit>,
+    timer: LatencyTimer,
+) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    let mut guard = StreamGuard {
+        _permit: permit,
+        timer: Some(timer),
+    };
+    create_sse_stream(response, ctx, initial_events, format).chain(stream::poll_fn(move |_| {
+        guard.complete();
+        std::task::Poll::Ready(None)
+    }))
+}
+
+/// 流式响应守卫
+///
+/// 与响应流同生命周期：持有并发许可，在流正常结束时记录延迟；
+/// 若在结束前被丢弃（客户端断开），记录日志说明上游请求已中止
+struct StreamGuard {
+    _permit: Option<OwnedSemaphorePermit>,
+    timer: Option<LatencyTimer>,
+}
+
+impl StreamGuard {
+    /// 标记流已正常结束
+    fn complete(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.finish();
+        }
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        if self.timer.is_some() {
+            tracing::info!("客户端在流结束前断开，已中止上游请求");
+        }
+    }
 }
 
 /// Ping 事件间隔（25秒）
 const PING_INTERVAL_SECS: u64 = 25;
 
-/// 创建 ping 事件的 SSE 字符串
-fn create_ping_sse() -> Bytes {
-    Bytes::from("event: ping\ndata: {\"type\": \"ping\"}\n\n")
+/// 按输出格式创建 ping 事件
+fn create_ping_sse(format: StreamFormat) -> Bytes {
+    Bytes::from(format.encode(&SseEvent::new("ping", json!({"type": "ping"}))))
 }
 
 /// 创建 SSE 事件流
@@ -220,12 +814,13 @@ fn create_sse_stream(
     response: reqwest::Response,
     ctx: StreamContext,
     initial_events: Vec<SseEvent>,
+    format: StreamFormat,
 ) -> impl Stream<Item = Result<Bytes, Infallible>> {
     // 先发送初始事件
     let initial_stream = stream::iter(
         initial_events
             .into_iter()
-            .map(|e| Ok(Bytes::from(e.to_sse_string()))),
+            .map(move |e| Ok(Bytes::from(format.encode(&e)))),
     );
 
     // 然后处理 Kiro 响应流，同时每25秒发送 ping 保活
@@ -233,7 +828,7 @@ fn create_sse_stream(
 
     let processing_stream = stream::unfold(
         (body_stream, ctx, EventStreamDecoder::new(), false, interval(Duration::from_secs(PING_INTERVAL_SECS))),
-        |(mut body_stream, mut ctx, mut decoder, finished, mut ping_interval)| async move {
+        move |(mut body_stream, mut ctx, mut decoder, finished, mut ping_interval)| async move {
             if finished {
                 return None;
             }
@@ -267,7 +862,7 @@ fn create_sse_stream(
                             // 转换为 SSE 字节流
                             let bytes: Vec<Result<Bytes, Infallible>> = events
                                 .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
+                                .map(|e| Ok(Bytes::from(format.encode(&e))))
                                 .collect();
 
                             Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
@@ -278,7 +873,7 @@ fn create_sse_stream(
                             let final_events = ctx.generate_final_events();
                             let bytes: Vec<Result<Bytes, Infallible>> = final_events
                                 .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
+                                .map(|e| Ok(Bytes::from(format.encode(&e))))
                                 .collect();
                             Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
                         }
@@ -287,7 +882,7 @@ fn create_sse_stream(
                             let final_events = ctx.generate_final_events();
                             let bytes: Vec<Result<Bytes, Infallible>> = final_events
                                 .into_iter()
-                                .map(|e| Ok(Bytes::from(e.to_sse_string())))
+                                .map(|e| Ok(Bytes::from(format.encode(&e))))
                                 .collect();
                             Some((stream::iter(bytes), (body_stream, ctx, decoder, true, ping_interval)))
                         }
@@ -296,7 +891,7 @@ fn create_sse_stream(
                 // 发送 ping 保活
                 _ = ping_interval.tick() => {
                     tracing::trace!("发送 ping 保活事件");
-                    let bytes: Vec<Result<Bytes, Infallible>> = vec![Ok(create_ping_sse())];
+                    let bytes: Vec<Result<Bytes, Infallible>> = vec![Ok(create_ping_sse(format))];
                     Some((stream::iter(bytes), (body_stream, ctx, decoder, false, ping_interval)))
                 }
             }
@@ -313,42 +908,48 @@ const CONTEXT_WINDOW_SIZE: i32 = 200_000;
 /// 处理非流式请求
 async fn handle_non_stream_request(
     provider: std::sync::Arc<tokio::sync::Mutex<crate::kiro::provider::KiroProvider>>,
-    request_body: &str,
-    model: &str,
+    timeouts: UpstreamTimeouts,
+    candidates: &[ModelCandidate],
     input_tokens: i32,
+    latency_tracker: std::sync::Arc<LatencyTracker>,
+    cache: Option<(std::sync::Arc<ResponseCache>, String)>,
+    locale: Locale,
 ) -> Response {
     // 调用 Kiro API
-    let response = {
-        let mut provider_guard = provider.lock().await;
-        match provider_guard.call_api(request_body).await {
-            Ok(resp) => resp,
+    let (response, served_model, timer) = {
+        let Ok(mut provider_guard) = lock_with_timeout(&provider, timeouts.queue).await else {
+            tracing::warn!("等待上游空闲超时，拒绝请求");
+            return queue_timeout_response(locale);
+        };
+        // 重试时间预算和延迟统计都从获取上游后开始计算，排队等待不计入
+        let deadline = provider_guard.retry_deadline();
+        let mut timer = latency_tracker.start(false);
+        match call_with_model_fallback(candidates, deadline, &mut *provider_guard, |p, body| {
+            Box::pin(p.call_api(body, timeouts.request, deadline))
+        })
+        .await
+        {
+            Ok((response, served_model)) => {
+                timer.mark_first_byte(&latency_model(&served_model));
+                (response, served_model, timer)
+            }
             Err(e) => {
                 tracing::error!("Kiro API 调用失败: {}", e);
-                return (
-                    StatusCode::BAD_GATEWAY,
-                    Json(ErrorResponse::new(
-                        "api_error",
-                        format!("上游 API 调用失败: {}", e),
-                    )),
-                )
-                    .into_response();
+                return upstream_error_response(ApiMessage::UpstreamFailed(e.to_string()), locale);
             }
         }
     };
+    let model = served_model.as_str();
 
     // 读取响应体
     let body_bytes = match response.bytes().await {
-        Ok(bytes) => bytes,
+        Ok(bytes) => {
+            timer.finish();
+            bytes
+        }
         Err(e) => {
             tracing::error!("读取响应体失败: {}", e);
-            return (
-                StatusCode::BAD_GATEWAY,
-                Json(ErrorResponse::new(
-                    "api_error",
-                    format!("读取响应失败: {}", e),
-                )),
-            )
-                .into_response();
+            return upstream_error_response(ApiMessage::ReadResponseFailed(e.to_string()), locale);
         }
     };
 
@@ -358,70 +959,12 @@ async fn handle_non_stream_request(
         tracing::warn!("缓冲区溢出: {}", e);
     }
 
-    let mut text_content = String::new();
-    let mut tool_uses: Vec<serde_json::Value> = Vec::new();
-    let mut has_tool_use = false;
-    let mut stop_reason = "end_turn".to_string();
-    // 从 contextUsageEvent 计算的实际输入 tokens
-    let mut context_input_tokens: Option<i32> = None;
-
-    // 收集工具调用的增量 JSON
-    let mut tool_json_buffers: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-
+    let mut events = Vec::new();
     for result in decoder.decode_iter() {
         match result {
             Ok(frame) => {
                 if let Ok(event) = Event::from_frame(frame) {
-                    match event {
-                        Event::AssistantResponse(resp) => {
-                            text_content.push_str(&resp.content);
-                        }
-                        Event::ToolUse(tool_use) => {
-                            has_tool_use = true;
-
-                            // 累积工具的 JSON 输入
-                            let buffer = tool_json_buffers
-                                .entry(tool_use.tool_use_id.clone())
-                                .or_insert_with(String::new);
-                            buffer.push_str(&tool_use.input);
-
-                            // 如果是完整的工具调用，添加到列表
-                            if tool_use.stop {
-                                let input: serde_json::Value = serde_json::from_str(buffer)
-                                    .unwrap_or_else(|e| {
-                                        tracing::warn!(
-                                            "工具输入 JSON 解析失败: {}, tool_use_id: {}, 原始内容: {}",
-                                            e, tool_use.tool_use_id, buffer
-                                        );
-                                        serde_json::json!({})
-                                    });
-
-                                tool_uses.push(json!({
-                                    "type": "tool_use",
-                                    "id": tool_use.tool_use_id,
-                                    "name": tool_use.name,
-                                    "input": input
-                                }));
-                            }
-                        }
-                        Event::ContextUsage(context_usage) => {
-                            // 从上下文使用百分比计算实际的 input_tokens
-                            // 公式: percentage * 200000 / 100 = percentage * 2000
-                            let actual_input_tokens = (context_usage.context_usage_percentage * (CONTEXT_WINDOW_SIZE as f64) / 100.0) as i32;
-                            context_input_tokens = Some(actual_input_tokens);
-                            tracing::debug!(
-                                "收到 contextUsageEvent: {}%, 计算 input_tokens: {}",
-                                context_usage.context_usage_percentage,
-                                actual_input_tokens
-                            );
-                        }
-                        Event::Exception { exception_type, .. } => {
-                            if exception_type == "ContentLengthExceededException" {
-                                stop_reason = "max_tokens".to_string();
-                            }
-                        }
-                        _ => {}
-                    }
+                    events.push(event);
                 }
             }
             Err(e) => {
@@ -429,11 +972,12 @@ async fn handle_non_stream_request(
             }
         }
     }
-
-    // 确定 stop_reason
-    if has_tool_use && stop_reason == "end_turn" {
-        stop_reason = "tool_use".to_string();
-    }
+    let CollectedResponse {
+        text_content,
+        tool_uses,
+        stop_reason,
+        context_input_tokens,
+    } = collect_events(events);
 
     // 构建响应内容
     let mut content: Vec<serde_json::Value> = Vec::new();
@@ -468,10 +1012,132 @@ async fn handle_non_stream_request(
         }
     });
 
-    (StatusCode::OK, Json(response_body)).into_response()
+    if let Some((cache, key)) = cache {
+        cache.insert(
+            key,
+            CachedResponse {
+                body: response_body.clone(),
+                served_model: model.to_string(),
+            },
+        );
+    }
+
+    let mut response = (StatusCode::OK, Json(response_body)).into_response();
+    mark_served_model(&mut response, &candidates[0].model, model);
+    response
+}
+
+
+
+/// 非流式响应的事件汇总结果
+struct CollectedResponse {
+    text_content: String,
+    tool_uses: Vec<serde_json::Value>,
+    stop_reason: String,
+    /// 从 contextUsageEvent 计算的实际输入 tokens
+    context_input_tokens: Option<i32>,
+}
+
+/// 正在拼接的工具调用
+struct PendingToolUse {
+    id: String,
+    name: String,
+    input: String,
+    complete: bool,
+}
+
+impl PendingToolUse {
+    fn into_content_block(self) -> serde_json::Value {
+        if !self.complete {
+            tracing::warn!("工具调用未收到结束标记，使用已收到的输入: {}", self.id);
+        }
+        let input = if self.input.trim().is_empty() {
+            json!({})
+        } else {
+            serde_json::from_str(&self.input).unwrap_or_else(|e| {
+                tracing::warn!(
+                    "工具输入 JSON 解析失败: {}, tool_use_id: {}, 原始内容: {}",
+                    e, self.id, self.input
+                );
+                json!({})
+            })
+        };
+        json!({
+            "type": "tool_use",
+            "id": self.id,
+            "name": self.name,
+            "input": input
+        })
+    }
 }
 
+/// 汇总上游事件：拼接文本、按首次出现顺序组装工具调用的增量 JSON、确定 stop_reason
+fn collect_events(events: impl IntoIterator<Item = Event>) -> CollectedResponse {
+    let mut text_content = String::new();
+    let mut pending_tools: Vec<PendingToolUse> = Vec::new();
+    let mut stop_reason = "end_turn".to_string();
+    let mut context_input_tokens: Option<i32> = None;
 
+    for event in events {
+        match event {
+            Event::AssistantResponse(resp) => {
+                text_content.push_str(&resp.content);
+            }
+            Event::ToolUse(tool_use) => {
+                // 累积工具的 JSON 输入
+                let index = match pending_tools.iter().position(|t| t.id == tool_use.tool_use_id) {
+                    Some(index) => index,
+                    None => {
+                        pending_tools.push(PendingToolUse {
+                            id: tool_use.tool_use_id.clone(),
+                            name: tool_use.name.clone(),
+                            input: String::new(),
+                            complete: false,
+                        });
+                        pending_tools.len() - 1
+                    }
+                };
+                let pending = &mut pending_tools[index];
+                if !pending.complete {
+                    pending.input.push_str(&tool_use.input);
+                    pending.complete = tool_use.stop;
+                }
+            }
+            Event::ContextUsage(context_usage) => {
+                // 从上下文使用百分比计算实际的 input_tokens
+                // 公式: percentage * 200000 / 100 = percentage * 2000
+                let actual_input_tokens = (context_usage.context_usage_percentage * (CONTEXT_WINDOW_SIZE as f64) / 100.0) as i32;
+                context_input_tokens = Some(actual_input_tokens);
+                tracing::debug!(
+                    "收到 contextUsageEvent: {}%, 计算 input_tokens: {}",
+                    context_usage.context_usage_percentage,
+                    actual_input_tokens
+                );
+            }
+            Event::Exception { exception_type, .. } => {
+                if exception_type == "ContentLengthExceededException" {
+                    stop_reason = "max_tokens".to_string();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 确定 stop_reason
+    if !pending_tools.is_empty() && stop_reason == "end_turn" {
+        stop_reason = "tool_use".to_string();
+    }
+
+    CollectedResponse {
+        text_content,
+        tool_uses: pending_tools
+            .into_iter()
+            .map(PendingToolUse::into_content_block)
+            .collect(),
+        stop_reason,
+        context_input_tokens,
+    }
+}
 
 /// POST /v1/messages/count_tokens
 ///
@@ -489,3 +1155,658 @@ pub async fn count_tokens(JsonExtractor(payload): JsonExtractor<CountTokensReque
         input_tokens: total_tokens.max(1) as i32,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_use_event(id: &str, input: &str, stop: bool) -> Event {
+        Event::ToolUse(crate::kiro::model::events::ToolUseEvent {
+            name: "get_weather".to_string(),
+            tool_use_id: id.to_string(),
+            input: input.to_string(),
+            stop,
+        })
+    }
+
+    fn text_event(content: &str) -> Event {
+        Event::AssistantResponse(serde_json::from_value(json!({ "content": content })).unwrap())
+    }
+
+    #[test]
+    fn test_collect_events_reassembles_tool_use_input() {
+        let collected = collect_events(vec![
+            text_event("Let me check."),
+            tool_use_event("tooluse_1", "", false),
+            tool_use_event("tooluse_1", "{\"city\": \"Par", false),
+            tool_use_event("tooluse_1", "is\", \"unit\": \"c\"}", false),
+            tool_use_event("tooluse_1", "", true),
+            tool_use_event("tooluse_2", "", true),
+        ]);
+
+        assert_eq!(collected.text_content, "Let me check.");
+        assert_eq!(collected.stop_reason, "tool_use");
+        assert_eq!(
+            collected.tool_uses,
+            vec![
+                json!({
+                    "type": "tool_use",
+                    "id": "tooluse_1",
+                    "name": "get_weather",
+                    "input": {"city": "Paris", "unit": "c"}
+                }),
+                json!({
+                    "type": "tool_use",
+                    "id": "tooluse_2",
+                    "name": "get_weather",
+                    "input": {}
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_events_keeps_tool_use_without_stop() {
+        let collected = collect_events(vec![tool_use_event("tooluse_1", "{\"city\": \"Paris\"}", false)]);
+
+        assert_eq!(collected.stop_reason, "tool_use");
+        assert_eq!(collected.tool_uses[0]["input"], json!({"city": "Paris"}));
+    }
+
+    #[test]
+    fn test_collect_events_text_only_ends_turn() {
+        let collected = collect_events(vec![text_event("Hello"), text_event(" world")]);
+
+        assert_eq!(collected.text_content, "Hello world");
+        assert!(collected.tool_uses.is_empty());
+        assert_eq!(collected.stop_reason, "end_turn");
+    }
+
+    #[test]
+    fn test_request_timeout_override() {
+        let max = Duration::from_secs(3600);
+        let with_header = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(TIMEOUT_OVERRIDE_HEADER, value.parse().unwrap());
+            request_timeout_override(&headers, max)
+        };
+
+        assert_eq!(request_timeout_override(&HeaderMap::new(), max), Ok(None));
+        assert_eq!(with_header("30"), Ok(Some(Duration::from_secs(30))));
+        // 超过上限时截断
+        assert_eq!(with_header("100000"), Ok(Some(max)));
+        assert!(with_header("0").is_err());
+        assert!(with_header("soon").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_conversion_error_is_localized() {
+        let message = |locale| async move {
+            let error = BuildRequestError::Conversion(ConversionError::UnsupportedModel(
+                "gpt-4".to_string(),
+            ));
+            let response = error.into_localized_response(locale);
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            body["error"]["message"].as_str().unwrap().to_string()
+        };
+
+        assert_eq!(message(Locale::En).await, "Unsupported model: gpt-4");
+        assert_eq!(message(Locale::Zh).await, "模型不支持: gpt-4");
+    }
+
+    #[tokio::test]
+    async fn test_request_errors_are_localized() {
+        let message = |body: serde_json::Value, accept_language: Option<&'static str>| async move {
+            let mut headers = HeaderMap::new();
+            if let Some(value) = accept_language {
+                headers.insert(header::ACCEPT_LANGUAGE, value.parse().unwrap());
+            }
+            let response = post_messages(
+                State(AppState::new("test-key")),
+                Query(MessagesQuery::default()),
+                headers,
+                JsonExtractor(body),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            body["error"]["message"].as_str().unwrap().to_string()
+        };
+
+        // 校验错误：字段路径保持不变，描述随语言切换（默认中文）
+        let invalid = json!({"model": "claude-sonnet-4", "max_tokens": 1024, "messages": []});
+        assert_eq!(message(invalid.clone(), None).await, "messages: 至少需要一条消息");
+        assert_eq!(
+            message(invalid, Some("en")).await,
+            "messages: At least one message is required"
+        );
+
+        // 反序列化错误
+        let malformed = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "stream": "yes",
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+        assert!(message(malformed.clone(), None).await.starts_with("请求体无效: "));
+        assert!(message(malformed, Some("en")).await.starts_with("Invalid request body: "));
+    }
+
+    #[test]
+    fn test_decode_event_stream_fixture() {
+        use crate::kiro::parser::frame::encode_event_frame;
+
+        let mut data = encode_event_frame("assistantResponseEvent", br#"{"content":"Hello"}"#);
+        data.extend(encode_event_frame(
+            "toolUseEvent",
+            br#"{"name":"get_weather","toolUseId":"t1","input":"{}","stop":true}"#,
+        ));
+        data.extend(encode_event_frame("contextUsageEvent", b"not json"));
+        // 末尾残留半个帧
+        data.extend_from_slice(&[0x00, 0x00, 0x00]);
+
+        let decoded = decode_event_stream(&data);
+        let frames = decoded["frames"].as_array().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0]["eventType"], "assistantResponseEvent");
+        assert_eq!(frames[0]["event"], "assistantResponse");
+        assert_eq!(frames[0]["payload"]["content"], "Hello");
+        assert_eq!(frames[1]["event"], "toolUse");
+        assert_eq!(frames[1]["payload"]["toolUseId"], "t1");
+        // 负载无法解析时保留原始字符串并给出错误
+        assert_eq!(frames[2]["payload"], "not json");
+        assert!(frames[2]["error"].is_string());
+        assert_eq!(decoded["trailingBytes"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_debug_decode_invalid_base64_is_localized() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_LANGUAGE, "zh-CN".parse().unwrap());
+        let request = DebugDecodeRequest {
+            data: "not base64!".to_string(),
+        };
+
+        let response = debug_decode(State(AppState::new("test-key")), headers, JsonExtractor(request)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["error"]["message"].as_str().unwrap().starts_with("data 不是有效的 base64"));
+    }
+
+    #[tokio::test]
+    async fn test_machine_id_follows_rotated_refresh_token() {
+        use crate::kiro::model::credentials::KiroCredentials;
+        use crate::kiro::provider::KiroProvider;
+        use crate::kiro::token_manager::TokenManager;
+        use crate::model::config::Config;
+
+        // 模拟上游：返回请求中 x-amz-user-agent 携带的 machine_id
+        let app = axum::Router::new().route(
+            "/generateAssistantResponse",
+            axum::routing::post(|headers: HeaderMap| async move {
+                let user_agent = headers["x-amz-user-agent"].to_str().unwrap().to_string();
+                user_agent.rsplit('-').next().unwrap().to_string()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let credentials = |refresh_token: &str| KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some(refresh_token.repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let config = Config {
+            upstream_base_override: Some(format!("http://{}/generateAssistantResponse", addr)),
+            ..Default::default()
+        };
+        let provider = KiroProvider::new(TokenManager::new(config, credentials("a"))).unwrap();
+        let state = AppState::new("test-key").with_kiro_provider(provider);
+
+        let machine_id = |state: AppState| async move {
+            let response = get_machine_id(State(state), HeaderMap::new()).await;
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()["machineId"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        let before = machine_id(state.clone()).await;
+
+        // 刷新 Token 轮换了 refreshToken，下一次请求使用新的 machine_id
+        let sent = {
+            let mut provider = state.kiro_provider.as_ref().unwrap().lock().await;
+            provider.set_credentials(credentials("b"));
+            let response = provider.call_api("{}", None, None).await.unwrap();
+            response.text().await.unwrap()
+        };
+
+        let after = machine_id(state).await;
+        assert_ne!(after, before);
+        assert_eq!(after, sent);
+    }
+
+    #[test]
+    fn test_negotiate_stream_format() {
+        let accept = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, value.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(negotiate_stream_format(&HeaderMap::new(), None), Ok(StreamFormat::Sse));
+        assert_eq!(
+            negotiate_stream_format(&HeaderMap::new(), Some("ndjson")),
+            Ok(StreamFormat::Ndjson)
+        );
+        assert_eq!(
+            negotiate_stream_format(&accept("application/x-ndjson; q=1.0"), None),
+            Ok(StreamFormat::Ndjson)
+        );
+        // 查询参数优先于 Accept 头
+        assert_eq!(
+            negotiate_stream_format(&accept("application/x-ndjson"), Some("sse")),
+            Ok(StreamFormat::Sse)
+        );
+        assert_eq!(
+            negotiate_stream_format(&accept("text/event-stream"), None),
+            Ok(StreamFormat::Sse)
+        );
+        assert!(negotiate_stream_format(&HeaderMap::new(), Some("xml")).is_err());
+    }
+
+    /// 从空的上游响应生成完整事件流，返回拼接后的输出
+    async fn render_empty_stream(format: StreamFormat) -> String {
+        let app = axum::Router::new().route("/", axum::routing::get(|| async { "" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        let response = reqwest::get(format!("http://{}/", addr)).await.unwrap();
+
+        let mut ctx = StreamContext::new_with_thinking("claude-sonnet-4", 10, false);
+        let initial_events = ctx.generate_initial_events();
+        let chunks: Vec<_> = create_sse_stream(response, ctx, initial_events, format)
+            .collect()
+            .await;
+        chunks
+            .into_iter()
+            .map(|chunk| String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stream_output_formats() {
+        let ndjson = render_empty_stream(StreamFormat::Ndjson).await;
+        let events: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.first().unwrap()["type"], "message_start");
+        assert_eq!(events.last().unwrap()["type"], "message_stop");
+        assert!(!ndjson.contains("event:"));
+
+        let sse = render_empty_stream(StreamFormat::Sse).await;
+        assert!(sse.starts_with("event: message_start\ndata: "));
+        assert!(sse.contains("event: message_stop\n"));
+    }
+
+    #[test]
+    fn test_apply_model_defaults_fills_only_missing_fields() {
+        let defaults = std::collections::HashMap::from([(
+            "claude-opus-4-5-20251101".to_string(),
+            json!({"max_tokens": 8192, "temperature": 0.5}),
+        )]);
+
+        let mut body = json!({
+            "model": "claude-opus-4-5-20251101",
+            "temperature": 1.0,
+            "messages": [{"role": "user", "content": "Hi"}]
+        });
+        apply_model_defaults(&mut body, &defaults);
+        assert_eq!(body["max_tokens"], 8192);
+        assert_eq!(body["temperature"], 1.0);
+
+        // 未配置默认值的模型保持不变
+        let mut other = json!({"model": "claude-sonnet-4", "messages": []});
+        apply_model_defaults(&mut other, &defaults);
+        assert!(other.get("max_tokens").is_none());
+    }
+
+    #[test]
+    fn test_version_info() {
+        let state = AppState::new("key").with_kiro_version("0.8.0");
+        let info = version_info(&state);
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.git_commit, option_env!("KIRO_GIT_COMMIT"));
+        assert_eq!(info.kiro_version, "0.8.0");
+    }
+
+    #[test]
+    fn test_stream_permit_unlimited_by_default() {
+        let state = AppState::new("key");
+        assert!(matches!(try_acquire_stream_permit(&state), Ok(None)));
+    }
+
+    #[test]
+    fn test_stream_permit_rejects_when_saturated() {
+        let state = AppState::new("key").with_max_concurrent_streams(2);
+
+        let first = try_acquire_stream_permit(&state).unwrap();
+        let second = try_acquire_stream_permit(&state).unwrap();
+        assert!(first.is_some() && second.is_some());
+
+        assert!(try_acquire_stream_permit(&state).is_err());
+
+        let response = stream_limit_exceeded_response(Locale::En);
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.headers().get(header::RETRY_AFTER).unwrap(),
+            &STREAM_RETRY_AFTER_SECS.to_string()
+        );
+
+        // 释放一个许可后可以再次获取
+        drop(first);
+        assert!(try_acquire_stream_permit(&state).unwrap().is_some());
+    }
+
+    /// 丢弃时通知测试的守卫，用于观察模拟上游的响应体何时被丢弃
+    struct NotifyOnDrop(Option<tokio::sync::oneshot::Sender<()>>);
+
+    impl Drop for NotifyOnDrop {
+        fn drop(&mut self) {
+            if let Some(tx) = self.0.take() {
+                let _ = tx.send(());
+            }
+        }
+    }
+
+    /// 启动一个永不结束的模拟上游，返回其地址和“上游响应体被丢弃”的通知
+    async fn spawn_endless_upstream() -> (String, tokio::sync::oneshot::Receiver<()>) {
+        use axum::{Router, routing::get};
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let tx = std::sync::Arc::new(std::sync::Mutex::new(Some(tx)));
+
+        let app = Router::new().route(
+            "/",
+            get(move || {
+                let guard = NotifyOnDrop(tx.lock().unwrap().take());
+                async move {
+                    let body = stream::unfold(guard, |guard| async move {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        Some((Ok::<_, Infallible>(Bytes::from_static(b"x")), guard))
+                    });
+                    Body::from_stream(body)
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/", addr), rx)
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_aborts_upstream() {
+        let (url, upstream_dropped) = spawn_endless_upstream().await;
+        let response = reqwest::get(&url).await.unwrap();
+
+        let state = AppState::new("key").with_max_concurrent_streams(1);
+        let permit = try_acquire_stream_permit(&state).unwrap();
+        let mut timer = state.latency_tracker.start(true);
+        timer.mark_first_byte("claude-sonnet-4.5");
+
+        let ctx = StreamContext::new_with_thinking("claude-sonnet-4", 1, false);
+        let mut sse = Box::pin(guarded_stream(response, ctx, Vec::new(), StreamFormat::Sse, permit, timer));
+
+        // 读取一次后模拟客户端断开；断开前并发许可由流持有
+        let _ = sse.next().await;
+        assert!(try_acquire_stream_permit(&state).is_err());
+        drop(sse);
+
+        tokio::time::timeout(Duration::from_secs(5), upstream_dropped)
+            .await
+            .expect("upstream request was not aborted")
+            .unwrap();
+
+        // 并发许可随流释放，且未完成的流不计入延迟统计
+        assert!(try_acquire_stream_permit(&state).unwrap().is_some());
+        assert!(state.latency_tracker.snapshot().is_empty());
+    }
+
+    fn candidates(models: &[&str]) -> Vec<ModelCandidate> {
+        models
+            .iter()
+            .map(|m| ModelCandidate {
+                model: m.to_string(),
+                request_body: format!("body-{}", m),
+            })
+            .collect()
+    }
+
+    fn upstream_error(status: u16, body: &str) -> anyhow::Error {
+        UpstreamStatusError {
+            label: "API 请求失败".to_string(),
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            body: body.to_string(),
+        }
+        .into()
+    }
+
+    const INVALID_MODEL_BODY: &str =
+        r#"{"message":"Invalid model. Please select a different model to continue.","reason":"INVALID_MODEL_ID"}"#;
+
+    #[tokio::test]
+    async fn test_model_fallback_after_primary_rejected() {
+        let candidates = candidates(&["claude-opus-4-5", "claude-sonnet-4-5"]);
+        let mut attempted: Vec<String> = Vec::new();
+
+        let (body, served) = call_with_model_fallback(&candidates, None, &mut attempted, |attempted, body| {
+            attempted.push(body.to_string());
+            let result = if body == "body-claude-opus-4-5" {
+                Err(upstream_error(400, INVALID_MODEL_BODY))
+            } else {
+                Ok(body.to_string())
+            };
+            Box::pin(async move { result })
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(served, "claude-sonnet-4-5");
+        assert_eq!(body, "body-claude-sonnet-4-5");
+        assert_eq!(attempted.len(), 2);
+
+        let mut response = StatusCode::OK.into_response();
+        mark_served_model(&mut response, "claude-opus-4-5", &served);
+        assert_eq!(
+            response.headers().get(SERVED_MODEL_HEADER).unwrap(),
+            "claude-sonnet-4-5"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_model_fallback_skipped_for_other_errors() {
+        let candidates = candidates(&["claude-opus-4-5", "claude-sonnet-4-5"]);
+        let mut attempts = 0;
+
+        let result = call_with_model_fallback(&candidates, None, &mut attempts, |attempts, _| {
+            *attempts += 1;
+            Box::pin(async { Err::<(), _>(upstream_error(500, "")) })
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        // 与模型无关的 400（如请求格式错误）不回退
+        let mut attempts = 0;
+        let result = call_with_model_fallback(&candidates, None, &mut attempts, |attempts, _| {
+            *attempts += 1;
+            let error = upstream_error(
+                400,
+                r#"{"message":"Improperly formed request.","reason":null}"#,
+            );
+            Box::pin(async { Err::<(), _>(error) })
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_model_fallback_shares_retry_deadline() {
+        let candidates = candidates(&["claude-opus-4-5", "claude-sonnet-4-5", "claude-haiku-4-5"]);
+        let budget = Duration::from_millis(100);
+        let started = Instant::now();
+        let deadline = started + budget;
+        let mut attempted: Vec<String> = Vec::new();
+
+        // 每次上游调用最多耗时 80ms（不超过截止时间），然后拒绝模型
+        let result = call_with_model_fallback(&candidates, Some(deadline), &mut attempted, |attempted, body| {
+            attempted.push(body.to_string());
+            let wait = Duration::from_millis(80).min(deadline.saturating_duration_since(Instant::now()));
+            Box::pin(async move {
+                tokio::time::sleep(wait).await;
+                Err::<(), _>(upstream_error(400, INVALID_MODEL_BODY))
+            })
+        })
+        .await;
+
+        // 前两个候选都失败后预算已用尽，第三个候选不再尝试，总耗时不超过一个预算
+        assert!(is_model_rejection(&result.unwrap_err()));
+        assert_eq!(attempted, vec!["body-claude-opus-4-5", "body-claude-sonnet-4-5"]);
+        assert!(started.elapsed() < budget + Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_latency_excludes_queue_wait_and_uses_served_model() {
+        use crate::kiro::model::credentials::KiroCredentials;
+        use crate::kiro::provider::KiroProvider;
+        use crate::kiro::token_manager::TokenManager;
+        use crate::model::config::Config;
+
+        // 模拟上游：拒绝 opus，其他模型返回空事件流
+        let app = axum::Router::new().route(
+            "/generateAssistantResponse",
+            axum::routing::post(|body: String| async move {
+                if body.contains("opus") {
+                    (StatusCode::BAD_REQUEST, INVALID_MODEL_BODY)
+                } else {
+                    (StatusCode::OK, "")
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let config = Config {
+            upstream_base_override: Some(format!("http://{}/generateAssistantResponse", addr)),
+            ..Default::default()
+        };
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let provider = KiroProvider::new(TokenManager::new(config, credentials)).unwrap();
+        let provider = std::sync::Arc::new(tokio::sync::Mutex::new(provider));
+
+        // 请求先排队等待 300ms，排队时间不计入上游延迟
+        let held = provider.clone().lock_owned().await;
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            drop(held);
+        });
+
+        let tracker = std::sync::Arc::new(LatencyTracker::new());
+        let timeouts = UpstreamTimeouts {
+            queue: None,
+            request: None,
+        };
+        let candidates = candidates(&["claude-opus-4-5", "claude-sonnet-4-5"]);
+        let response =
+            handle_non_stream_request(provider, timeouts, &candidates, 1, tracker.clone(), None, Locale::En)
+                .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let stats = tracker.snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].model, "claude-sonnet-4.5");
+        assert!(stats[0].total_ms.p50 < 300.0);
+    }
+
+    #[test]
+    fn test_model_rejection_requires_model_reason() {
+        assert!(is_model_rejection(&upstream_error(400, INVALID_MODEL_BODY)));
+        assert!(is_model_rejection(&upstream_error(
+            404,
+            r#"{"message":"Invalid model requested"}"#
+        )));
+        assert!(!is_model_rejection(&upstream_error(
+            400,
+            r#"{"message":"Input is too long.","reason":"CONTENT_LENGTH_EXCEEDS_THRESHOLD"}"#
+        )));
+        assert!(!is_model_rejection(&upstream_error(400, "Bad Request")));
+        assert!(!is_model_rejection(&upstream_error(500, INVALID_MODEL_BODY)));
+    }
+
+    #[test]
+    fn test_served_model_header_absent_without_substitution() {
+        let mut response = StatusCode::OK.into_response();
+        mark_served_model(&mut response, "claude-opus-4-5", "claude-opus-4-5");
+        assert!(response.headers().get(SERVED_MODEL_HEADER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_proceeds_when_released() {
+        let mutex = std::sync::Arc::new(tokio::sync::Mutex::new(()));
+        let held = mutex.clone().lock_owned().await;
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(held);
+        });
+
+        assert!(
+            lock_with_timeout(&mutex, Some(Duration::from_secs(5)))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_times_out() {
+        let mutex = tokio::sync::Mutex::new(());
+        let _held = mutex.lock().await;
+
+        assert!(
+            lock_with_timeout(&mutex, Some(Duration::from_millis(50)))
+                .await
+                .is_err()
+        );
+
+        let response = queue_timeout_response(Locale::En);
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+    }
+}
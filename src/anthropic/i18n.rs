@@ -0,0 +1,230 @@
+//! API 错误信息本地化
+//!
+//! 错误信息按 [`ApiMessage`] 枚举定义，根据请求的 `Accept-Language` 头（未指定或不支持时使用
+//! 配置的 `errorLocale`）输出英文或中文
+
+use axum::http::{HeaderMap, header};
+
+/// 错误信息语言
+///
+/// 默认中文，与引入本地化之前的错误信息保持一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    En,
+    #[default]
+    Zh,
+}
+
+impl Locale {
+    /// 解析语言标签，只看主语言部分（如 `zh-CN` 视为 `zh`），不支持的语言返回 `None`
+    pub fn parse(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Locale::En),
+            "zh" => Some(Locale::Zh),
+            _ => None,
+        }
+    }
+
+    /// 按 `Accept-Language` 头选择语言，取权重最高的受支持语言，没有时返回 `default`
+    pub fn from_headers(headers: &HeaderMap, default: Locale) -> Self {
+        let mut best: Option<(Locale, f32)> = None;
+        let entries = headers
+            .get_all(header::ACCEPT_LANGUAGE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+        for entry in entries {
+            let mut parts = entry.split(';');
+            let Some(locale) = parts.next().and_then(Locale::parse) else {
+                continue;
+            };
+            let weight = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if weight > 0.0 && best.is_none_or(|(_, w)| weight > w) {
+                best = Some((locale, weight));
+            }
+        }
+        best.map(|(locale, _)| locale).unwrap_or(default)
+    }
+}
+
+/// 返回给客户端的错误信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiMessage {
+    /// 未配置 KiroProvider
+    ProviderNotConfigured,
+    /// 请求命中屏蔽词
+    ContentBlocked,
+    /// 等待上游空闲超时
+    QueueTimeout,
+    /// 流式连接数已达上限
+    TooManyStreams,
+    /// `format` 查询参数无效
+    InvalidStreamFormat,
+    /// 超时覆盖请求头无效（参数为请求头名称）
+    InvalidTimeoutOverride(&'static str),
+    /// 模型不支持
+    UnsupportedModel(String),
+    /// 消息列表为空
+    EmptyMessages,
+    /// 序列化 Kiro 请求失败
+    SerializeFailed(String),
+    /// 调用上游失败
+    UpstreamFailed(String),
+    /// 读取上游响应失败
+    ReadResponseFailed(String),
+    /// `data` 不是有效的 base64
+    InvalidBase64(String),
+    /// 请求体不是 JSON 对象
+    BodyNotObject,
+    /// 缺少必填字段
+    FieldRequired,
+    /// 字段不能为空
+    FieldEmpty,
+    /// 字段应为字符串
+    ExpectedString,
+    /// 字段应为正整数
+    ExpectedPositiveInteger,
+    /// 字段应为数组
+    ExpectedList,
+    /// 字段应为对象
+    ExpectedObject,
+    /// 消息列表为空（请求校验阶段）
+    MessagesRequired,
+    /// 消息角色无效（参数为请求中的角色）
+    InvalidRole(String),
+    /// 请求体无法解析为 Messages 请求
+    InvalidRequestBody(String),
+    /// `globalRateLimit` 为 0，暂时拒绝所有请求
+    RequestsBlocked,
+    /// 触发全局限流
+    RateLimited,
+}
+
+impl ApiMessage {
+    /// 按语言输出错误信息
+    pub fn text(&self, locale: Locale) -> String {
+        match (self, locale) {
+            (ApiMessage::ProviderNotConfigured, Locale::En) => {
+                "Kiro API provider not configured".to_string()
+            }
+            (ApiMessage::ProviderNotConfigured, Locale::Zh) => "Kiro API Provider 未配置".to_string(),
+            (ApiMessage::ContentBlocked, Locale::En) => {
+                "Request content is blocked by the gateway content policy".to_string()
+            }
+            (ApiMessage::ContentBlocked, Locale::Zh) => "请求内容被网关内容策略拦截".to_string(),
+            (ApiMessage::QueueTimeout, Locale::En) => {
+                "Timed out waiting for an available upstream connection".to_string()
+            }
+            (ApiMessage::QueueTimeout, Locale::Zh) => "等待可用的上游连接超时".to_string(),
+            (ApiMessage::TooManyStreams, Locale::En) => {
+                "Too many concurrent streaming connections".to_string()
+            }
+            (ApiMessage::TooManyStreams, Locale::Zh) => "并发流式连接数已达上限".to_string(),
+            (ApiMessage::InvalidStreamFormat, Locale::En) => {
+                "format must be one of: sse, ndjson".to_string()
+            }
+            (ApiMessage::InvalidStreamFormat, Locale::Zh) => "format 只能是 sse 或 ndjson".to_string(),
+            (ApiMessage::InvalidTimeoutOverride(name), Locale::En) => {
+                format!("{} must be a positive integer", name)
+            }
+            (ApiMessage::InvalidTimeoutOverride(name), Locale::Zh) => {
+                format!("{} 必须是正整数", name)
+            }
+            (ApiMessage::UnsupportedModel(model), Locale::En) => {
+                format!("Unsupported model: {}", model)
+            }
+            (ApiMessage::UnsupportedModel(model), Locale::Zh) => format!("模型不支持: {}", model),
+            (ApiMessage::EmptyMessages, Locale::En) => "messages must not be empty".to_string(),
+            (ApiMessage::EmptyMessages, Locale::Zh) => "消息列表为空".to_string(),
+            (ApiMessage::SerializeFailed(e), Locale::En) => {
+                format!("Failed to serialize request: {}", e)
+            }
+            (ApiMessage::SerializeFailed(e), Locale::Zh) => format!("序列化请求失败: {}", e),
+            (ApiMessage::UpstreamFailed(e), Locale::En) => format!("Upstream API call failed: {}", e),
+            (ApiMessage::UpstreamFailed(e), Locale::Zh) => format!("上游 API 调用失败: {}", e),
+            (ApiMessage::ReadResponseFailed(e), Locale::En) => {
+                format!("Failed to read upstream response: {}", e)
+            }
+            (ApiMessage::ReadResponseFailed(e), Locale::Zh) => format!("读取响应失败: {}", e),
+            (ApiMessage::InvalidBase64(e), Locale::En) => format!("data is not valid base64: {}", e),
+            (ApiMessage::InvalidBase64(e), Locale::Zh) => format!("data 不是有效的 base64: {}", e),
+            (ApiMessage::BodyNotObject, Locale::En) => {
+                "Request body must be a JSON object".to_string()
+            }
+            (ApiMessage::BodyNotObject, Locale::Zh) => "请求体必须是 JSON 对象".to_string(),
+            (ApiMessage::FieldRequired, Locale::En) => "Field required".to_string(),
+            (ApiMessage::FieldRequired, Locale::Zh) => "缺少必填字段".to_string(),
+            (ApiMessage::FieldEmpty, Locale::En) => "Must not be empty".to_string(),
+            (ApiMessage::FieldEmpty, Locale::Zh) => "不能为空".to_string(),
+            (ApiMessage::ExpectedString, Locale::En) => "Input should be a valid string".to_string(),
+            (ApiMessage::ExpectedString, Locale::Zh) => "必须是字符串".to_string(),
+            (ApiMessage::ExpectedPositiveInteger, Locale::En) => {
+                "Input should be a positive integer".to_string()
+            }
+            (ApiMessage::ExpectedPositiveInteger, Locale::Zh) => "必须是正整数".to_string(),
+            (ApiMessage::ExpectedList, Locale::En) => "Input should be a valid list".to_string(),
+            (ApiMessage::ExpectedList, Locale::Zh) => "必须是数组".to_string(),
+            (ApiMessage::ExpectedObject, Locale::En) => "Input should be a valid object".to_string(),
+            (ApiMessage::ExpectedObject, Locale::Zh) => "必须是对象".to_string(),
+            (ApiMessage::MessagesRequired, Locale::En) => {
+                "At least one message is required".to_string()
+            }
+            (ApiMessage::MessagesRequired, Locale::Zh) => "至少需要一条消息".to_string(),
+            (ApiMessage::InvalidRole(role), Locale::En) => {
+                format!("Input should be 'user' or 'assistant', got '{}'", role)
+            }
+            (ApiMessage::InvalidRole(role), Locale::Zh) => {
+                format!("只能是 'user' 或 'assistant'，实际为 '{}'", role)
+            }
+            (ApiMessage::InvalidRequestBody(e), Locale::En) => format!("Invalid request body: {}", e),
+            (ApiMessage::InvalidRequestBody(e), Locale::Zh) => format!("请求体无效: {}", e),
+            (ApiMessage::RequestsBlocked, Locale::En) => {
+                "Requests are temporarily blocked by the gateway".to_string()
+            }
+            (ApiMessage::RequestsBlocked, Locale::Zh) => "网关已暂时拒绝所有请求".to_string(),
+            (ApiMessage::RateLimited, Locale::En) => "Global rate limit exceeded".to_string(),
+            (ApiMessage::RateLimited, Locale::Zh) => "超出全局请求速率限制".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept_language(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_LANGUAGE, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_locale_from_accept_language() {
+        assert_eq!(Locale::from_headers(&HeaderMap::new(), Locale::Zh), Locale::Zh);
+        assert_eq!(Locale::from_headers(&accept_language("zh-CN"), Locale::En), Locale::Zh);
+        assert_eq!(
+            Locale::from_headers(&accept_language("fr-FR, zh;q=0.5, en;q=0.8"), Locale::Zh),
+            Locale::En
+        );
+        // 不支持的语言回退到默认语言
+        assert_eq!(Locale::from_headers(&accept_language("de"), Locale::Zh), Locale::Zh);
+        assert_eq!(Locale::parse("ZH_tw"), Some(Locale::Zh));
+    }
+
+    #[test]
+    fn test_same_error_in_both_locales() {
+        let message = ApiMessage::UnsupportedModel("gpt-4".to_string());
+        assert_eq!(message.text(Locale::En), "Unsupported model: gpt-4");
+        assert_eq!(message.text(Locale::Zh), "模型不支持: gpt-4");
+
+        assert_eq!(
+            ApiMessage::QueueTimeout.text(Locale::En),
+            "Timed out waiting for an available upstream connection"
+        );
+        assert_eq!(ApiMessage::QueueTimeout.text(Locale::Zh), "等待可用的上游连接超时");
+    }
+}
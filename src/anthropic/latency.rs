@@ -0,0 +1,228 @@
+//! 上游延迟统计
+//!
+//! 按模型（映射后的 Kiro 模型）、流式/非流式分别记录每个请求的上游延迟（首字节时间与完成时间），
+//! 每组仅保留最近 `MAX_SAMPLES_PER_KEY` 个样本，并据此计算滚动 p50/p95/p99。
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// 每组（模型 + 是否流式）保留的最大样本数
+const MAX_SAMPLES_PER_KEY: usize = 1024;
+
+/// 样本分组键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LatencyKey {
+    model: String,
+    stream: bool,
+}
+
+/// 单次请求的延迟样本
+#[derive(Debug, Clone, Copy)]
+struct LatencySample {
+    /// 从发出请求到收到上游响应头的时间
+    first_byte: Duration,
+    /// 从发出请求到响应体读取完毕的时间
+    total: Duration,
+}
+
+/// 延迟统计器
+#[derive(Default)]
+pub struct LatencyTracker {
+    samples: Mutex<HashMap<LatencyKey, VecDeque<LatencySample>>>,
+}
+
+/// 百分位数（毫秒）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// 单组延迟统计结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStats {
+    pub model: String,
+    pub stream: bool,
+    /// 当前窗口内的样本数
+    pub count: usize,
+    /// 首字节时间百分位（毫秒）
+    pub first_byte_ms: Percentiles,
+    /// 完成时间百分位（毫秒）
+    pub total_ms: Percentiles,
+}
+
+impl LatencyTracker {
+    /// 创建空的统计器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一个样本，超出窗口大小时丢弃最旧的样本
+    fn record(&self, model: &str, stream: bool, first_byte: Duration, total: Duration) {
+        let key = LatencyKey {
+            model: model.to_string(),
+            stream,
+        };
+        let mut samples = self.samples.lock().unwrap();
+        let window = samples.entry(key).or_default();
+        if window.len() >= MAX_SAMPLES_PER_KEY {
+            window.pop_front();
+        }
+        window.push_back(LatencySample { first_byte, total });
+    }
+
+    /// 计算当前所有分组的百分位统计，按模型名和流式标志排序
+    pub fn snapshot(&self) -> Vec<LatencyStats> {
+        let samples = self.samples.lock().unwrap();
+        let mut stats: Vec<LatencyStats> = samples
+            .iter()
+            .filter(|(_, window)| !window.is_empty())
+            .map(|(key, window)| LatencyStats {
+                model: key.model.clone(),
+                stream: key.stream,
+                count: window.len(),
+                first_byte_ms: percentiles(window.iter().map(|s| s.first_byte)),
+                total_ms: percentiles(window.iter().map(|s| s.total)),
+            })
+            .collect();
+        stats.sort_by(|a, b| a.model.cmp(&b.model).then(a.stream.cmp(&b.stream)));
+        stats
+    }
+
+    /// 开始计时一个上游请求（应在获取上游之后调用，排队等待不计入延迟）
+    pub fn start(self: &Arc<Self>, stream: bool) -> LatencyTimer {
+        LatencyTimer {
+            tracker: self.clone(),
+            stream,
+            started: Instant::now(),
+            first_byte: None,
+        }
+    }
+}
+
+/// 单个请求的计时器
+///
+/// 只有标记了首字节并调用 `finish` 才会记录样本，失败或被客户端中断的请求不计入统计
+pub struct LatencyTimer {
+    tracker: Arc<LatencyTracker>,
+    stream: bool,
+    started: Instant,
+    /// 首字节时间及实际响应的模型
+    first_byte: Option<(Duration, String)>,
+}
+
+impl LatencyTimer {
+    /// 标记收到上游首字节（响应头），样本按实际响应的模型分组（模型回退时与请求的模型不同）
+    pub fn mark_first_byte(&mut self, model: &str) {
+        self.first_byte = Some((self.started.elapsed(), model.to_string()));
+    }
+
+    /// 标记请求完成并记录样本
+    pub fn finish(self) {
+        let total = self.started.elapsed();
+        if let Some((first_byte, model)) = &self.first_byte {
+            self.tracker.record(model, self.stream, *first_byte, total);
+        }
+    }
+}
+
+/// 计算一组时长的 p50/p95/p99（最近秩法，单位毫秒）
+fn percentiles(durations: impl Iterator<Item = Duration>) -> Percentiles {
+    let mut values: Vec<f64> = durations.map(|d| d.as_secs_f64() * 1000.0).collect();
+    values.sort_by(|a, b| a.total_cmp(b));
+
+    Percentiles {
+        p50: nearest_rank(&values, 50.0),
+        p95: nearest_rank(&values, 95.0),
+        p99: nearest_rank(&values, 99.0),
+    }
+}
+
+fn nearest_rank(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_percentiles_of_known_samples() {
+        let tracker = LatencyTracker::new();
+        for i in 1..=100 {
+            tracker.record("claude-sonnet-4", false, ms(i), ms(i * 10));
+        }
+
+        let stats = tracker.snapshot();
+        assert_eq!(stats.len(), 1);
+        let stats = &stats[0];
+        assert_eq!(stats.count, 100);
+        assert!((stats.first_byte_ms.p50 - 50.0).abs() < 0.5);
+        assert!((stats.first_byte_ms.p95 - 95.0).abs() < 0.5);
+        assert!((stats.first_byte_ms.p99 - 99.0).abs() < 0.5);
+        assert!((stats.total_ms.p50 - 500.0).abs() < 5.0);
+        assert!((stats.total_ms.p99 - 990.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn test_stream_and_non_stream_tracked_separately() {
+        let tracker = LatencyTracker::new();
+        tracker.record("claude-sonnet-4", false, ms(100), ms(200));
+        tracker.record("claude-sonnet-4", true, ms(10), ms(5000));
+
+        let stats = tracker.snapshot();
+        assert_eq!(stats.len(), 2);
+        assert!(!stats[0].stream);
+        assert_eq!(stats[0].total_ms.p50, 200.0);
+        assert!(stats[1].stream);
+        assert_eq!(stats[1].total_ms.p50, 5000.0);
+    }
+
+    #[test]
+    fn test_window_is_bounded() {
+        let tracker = LatencyTracker::new();
+        for _ in 0..MAX_SAMPLES_PER_KEY {
+            tracker.record("claude-sonnet-4", false, ms(1000), ms(1000));
+        }
+        // 新样本挤掉最旧的样本
+        for _ in 0..MAX_SAMPLES_PER_KEY {
+            tracker.record("claude-sonnet-4", false, ms(10), ms(10));
+        }
+
+        let stats = tracker.snapshot();
+        assert_eq!(stats[0].count, MAX_SAMPLES_PER_KEY);
+        assert_eq!(stats[0].first_byte_ms.p99, 10.0);
+    }
+
+    #[test]
+    fn test_timer_records_on_finish() {
+        let tracker = Arc::new(LatencyTracker::new());
+        let mut timer = tracker.start(true);
+        timer.mark_first_byte("claude-haiku-4.5");
+        timer.finish();
+
+        // 未 finish 或未收到首字节的计时器不计入统计
+        let mut abandoned = tracker.start(true);
+        abandoned.mark_first_byte("claude-haiku-4.5");
+        tracker.start(true).finish();
+
+        let stats = tracker.snapshot();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].model, "claude-haiku-4.5");
+        assert_eq!(stats[0].count, 1);
+        assert!(stats[0].first_byte_ms.p50 <= stats[0].total_ms.p50);
+    }
+}
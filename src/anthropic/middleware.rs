@@ -1,29 +1,120 @@
 //! Anthropic API 中间件
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 
 use axum::{
     body::Body,
     extract::State,
-    http::{header, Request, StatusCode},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Json, Response},
 };
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Semaphore};
 
+use crate::kiro::clock_skew::ClockSkewMonitor;
 use crate::kiro::provider::KiroProvider;
+use crate::kiro::throttle::ThrottleTracker;
+use crate::model::config::default_max_request_timeout_secs;
 
-use super::types::ErrorResponse;
+use super::guardrails::Guardrails;
+use super::i18n::{ApiMessage, Locale};
+use super::latency::LatencyTracker;
+use super::response_cache::ResponseCache;
+use super::rate_limit::{retry_after_secs, TokenBucket};
+use super::types::{ErrorResponse, MaintenanceStatus};
+
+/// 维护模式未指定提示时的默认消息
+const DEFAULT_MAINTENANCE_MESSAGE: &str =
+    "The service is under maintenance, please try again later";
+
+/// 维护模式开关
+///
+/// 开启后 `/v1` 请求统一返回 503，管理端点不受影响
+pub struct MaintenanceMode {
+    enabled: AtomicBool,
+    message: RwLock<String>,
+}
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            message: RwLock::new(DEFAULT_MAINTENANCE_MESSAGE.to_string()),
+        }
+    }
+}
+
+impl MaintenanceMode {
+    /// 开启或关闭维护模式，未提供提示时使用默认消息
+    pub fn set(&self, enabled: bool, message: Option<String>) {
+        *self.message.write().unwrap() =
+            message.unwrap_or_else(|| DEFAULT_MAINTENANCE_MESSAGE.to_string());
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// 维护模式开启时返回提示消息
+    pub fn active_message(&self) -> Option<String> {
+        self.enabled
+            .load(Ordering::SeqCst)
+            .then(|| self.message.read().unwrap().clone())
+    }
+
+    /// 当前状态
+    pub fn status(&self) -> MaintenanceStatus {
+        MaintenanceStatus {
+            enabled: self.enabled.load(Ordering::SeqCst),
+            message: self.message.read().unwrap().clone(),
+        }
+    }
+}
 
 /// 应用共享状态
 #[derive(Clone)]
 pub struct AppState {
     /// API 密钥
     pub api_key: String,
+    /// 管理端点 API 密钥（可选，不设置时管理端点不可用）
+    pub admin_api_key: Option<String>,
     /// Kiro Provider（可选，用于实际 API 调用）
     pub kiro_provider: Option<Arc<Mutex<KiroProvider>>>,
+    /// 上游 429 比例统计（与 Provider 共享，读取时无需等待 Provider 锁）
+    pub throttle_tracker: Option<Arc<ThrottleTracker>>,
+    /// 当前凭证派生的 machine_id（与 Provider 共享，刷新 Token 后更新，读取时无需等待 Provider 锁）
+    pub machine_id: Arc<RwLock<Option<String>>>,
     /// Profile ARN（可选，用于请求）
     pub profile_arn: Option<String>,
+    /// 时钟偏差监控器（可选，用于 /healthz）
+    pub clock_skew_monitor: Option<ClockSkewMonitor>,
+    /// 流式连接信号量（可选，用于限制并发流式连接数）
+    pub stream_semaphore: Option<Arc<Semaphore>>,
+    /// 全局限流令牌桶（可选，用于限制网关整体请求速率）
+    pub rate_limiter: Option<Arc<TokenBucket>>,
+    /// 上游延迟统计
+    pub latency_tracker: Arc<LatencyTracker>,
+    /// 确定性请求响应缓存（可选，默认关闭）
+    pub response_cache: Option<Arc<ResponseCache>>,
+    /// 模型回退链（请求模型 -> 备选模型列表），默认为空
+    pub model_fallbacks: Arc<HashMap<String, Vec<String>>>,
+    /// 按模型注入的默认请求参数，默认为空
+    pub model_defaults: Arc<HashMap<String, serde_json::Value>>,
+    /// 请求内容策略（系统提示词注入、屏蔽词），默认不启用
+    pub guardrails: Arc<Guardrails>,
+    /// 请求未通过 `Accept-Language` 指定受支持语言时，错误信息使用的语言
+    pub default_locale: Locale,
+    /// 等待上游空闲的排队超时（可选，不设置则一直等待）
+    pub queue_timeout: Option<std::time::Duration>,
+    /// 附加到 `/v1` 响应上的自定义响应头
+    pub extra_response_headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+    /// 配置的 Kiro 版本（用于 /version）
+    pub kiro_version: String,
+    /// 维护模式开关（通过 `/admin/maintenance` 切换）
+    pub maintenance: Arc<MaintenanceMode>,
+    /// 认证通过后从入站请求中移除的头
+    pub strip_request_headers: Arc<Vec<HeaderName>>,
+    /// `x-kiro-timeout-secs` 请求头允许的最大单次请求超时
+    pub max_request_timeout: std::time::Duration,
 }
 
 impl AppState {
@@ -31,13 +122,39 @@ impl AppState {
     pub fn new(api_key: impl Into<String>) -> Self {
         Self {
             api_key: api_key.into(),
+            admin_api_key: None,
             kiro_provider: None,
+            throttle_tracker: None,
+            machine_id: Arc::default(),
             profile_arn: None,
+            clock_skew_monitor: None,
+            stream_semaphore: None,
+            rate_limiter: None,
+            latency_tracker: Arc::new(LatencyTracker::new()),
+            response_cache: None,
+            model_fallbacks: Arc::new(HashMap::new()),
+            model_defaults: Arc::new(HashMap::new()),
+            guardrails: Arc::new(Guardrails::default()),
+            default_locale: Locale::default(),
+            queue_timeout: None,
+            extra_response_headers: Arc::new(Vec::new()),
+            kiro_version: String::new(),
+            maintenance: Arc::new(MaintenanceMode::default()),
+            strip_request_headers: Arc::new(Vec::new()),
+            max_request_timeout: std::time::Duration::from_secs(default_max_request_timeout_secs()),
         }
     }
 
+    /// 设置管理端点 API 密钥
+    pub fn with_admin_api_key(mut self, key: impl Into<String>) -> Self {
+        self.admin_api_key = Some(key.into());
+        self
+    }
+
     /// 设置 KiroProvider
     pub fn with_kiro_provider(mut self, provider: KiroProvider) -> Self {
+        self.throttle_tracker = Some(provider.throttle_tracker());
+        self.machine_id = provider.machine_id_handle();
         self.kiro_provider = Some(Arc::new(Mutex::new(provider)));
         self
     }
@@ -47,6 +164,119 @@ impl AppState {
         self.profile_arn = Some(arn.into());
         self
     }
+
+    /// 设置 Kiro 版本
+    pub fn with_kiro_version(mut self, version: impl Into<String>) -> Self {
+        self.kiro_version = version.into();
+        self
+    }
+
+    /// 设置时钟偏差监控器
+    pub fn with_clock_skew_monitor(mut self, monitor: ClockSkewMonitor) -> Self {
+        self.clock_skew_monitor = Some(monitor);
+        self
+    }
+
+    /// 设置最大并发流式连接数
+    pub fn with_max_concurrent_streams(mut self, max: usize) -> Self {
+        self.stream_semaphore = Some(Arc::new(Semaphore::new(max)));
+        self
+    }
+
+    /// 设置全局请求速率上限（每秒请求数）
+    pub fn with_global_rate_limit(mut self, rate_per_sec: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(TokenBucket::new(rate_per_sec)));
+        self
+    }
+
+    /// 设置模型回退链
+    pub fn with_model_fallbacks(mut self, fallbacks: HashMap<String, Vec<String>>) -> Self {
+        self.model_fallbacks = Arc::new(fallbacks);
+        self
+    }
+
+    /// 获取请求模型的完整回退链（请求的模型在前，配置的备选模型在后）
+    pub fn model_chain(&self, model: &str) -> Vec<String> {
+        let mut chain = vec![model.to_string()];
+        if let Some(fallbacks) = self.model_fallbacks.get(model) {
+            chain.extend(fallbacks.iter().filter(|m| *m != model).cloned());
+        }
+        chain
+    }
+
+    /// 设置按模型注入的默认请求参数
+    pub fn with_model_defaults(mut self, defaults: HashMap<String, serde_json::Value>) -> Self {
+        self.model_defaults = Arc::new(defaults);
+        self
+    }
+
+    /// 设置错误信息的默认语言
+    pub fn with_default_locale(mut self, locale: Locale) -> Self {
+        self.default_locale = locale;
+        self
+    }
+
+    /// 设置请求内容策略
+    pub fn with_guardrails(mut self, guardrails: Guardrails) -> Self {
+        self.guardrails = Arc::new(guardrails);
+        self
+    }
+
+    /// 设置排队超时
+    pub fn with_queue_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.queue_timeout = Some(timeout);
+        self
+    }
+
+    /// 设置 `x-kiro-timeout-secs` 请求头允许的最大单次请求超时
+    pub fn with_max_request_timeout(mut self, max: std::time::Duration) -> Self {
+        self.max_request_timeout = max;
+        self
+    }
+
+    /// 设置自定义响应头
+    ///
+    /// 非法的头名称或值会被跳过（启动时的配置校验已拦截此类配置）
+    pub fn with_extra_response_headers(mut self, headers: &HashMap<String, String>) -> Self {
+        let parsed = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                match (HeaderName::try_from(name.as_str()), HeaderValue::try_from(value.as_str())) {
+                    (Ok(name), Ok(value)) => Some((name, value)),
+                    _ => {
+                        tracing::warn!("忽略非法的自定义响应头: {}", name);
+                        None
+                    }
+                }
+            })
+            .collect();
+        self.extra_response_headers = Arc::new(parsed);
+        self
+    }
+
+    /// 设置需要从入站请求中移除的头
+    ///
+    /// 非法的头名称会被跳过（启动时的配置校验已拦截此类配置）
+    pub fn with_strip_request_headers(mut self, names: &[String]) -> Self {
+        let parsed = names
+            .iter()
+            .filter_map(|name| match HeaderName::try_from(name.as_str()) {
+                Ok(name) => Some(name),
+                Err(_) => {
+                    tracing::warn!("忽略非法的请求头名称: {}", name);
+                    None
+                }
+            })
+            .collect();
+        self.strip_request_headers = Arc::new(parsed);
+        self
+    }
+
+    /// 启用确定性请求响应缓存
+    pub fn with_response_cache(mut self, ttl: std::time::Duration) -> Self {
+        self.response_cache = Some(Arc::new(ResponseCache::new(ttl)));
+        self
+    }
 }
 
 /// 从请求中提取 API Key
@@ -116,6 +346,173 @@ pub async fn auth_middleware(
     }
 }
 
+/// 管理端点认证中间件
+///
+/// 只接受 `adminApiKey`；未配置时拒绝所有请求，普通 API Key 不能访问管理端点
+pub async fn admin_auth_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let expected = state.admin_api_key.as_deref();
+    match (extract_api_key(&request), expected) {
+        (Some(key), Some(expected)) if constant_time_eq(&key, expected) => next.run(request).await,
+        _ => {
+            let error = ErrorResponse::authentication_error();
+            (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+        }
+    }
+}
+
+/// 全局限流中间件
+///
+/// 令牌桶为空时返回 429，并通过 `Retry-After` 告知客户端等待时间；
+/// 无论是否限流，响应都会带上 `x-ratelimit-*` 头
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(limiter) = &state.rate_limiter else {
+        return next.run(request).await;
+    };
+    let locale = Locale::from_headers(request.headers(), state.default_locale);
+
+    match limiter.try_acquire() {
+        Ok(status) => {
+            let mut response = next.run(request).await;
+            status.apply_headers(response.headers_mut());
+            response
+        }
+        Err(limited) if limiter.is_blocked() => {
+            tracing::warn!("globalRateLimit 为 0，拒绝请求");
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(ErrorResponse::new(
+                    "rate_limit_error",
+                    ApiMessage::RequestsBlocked.text(locale),
+                )),
+            )
+                .into_response();
+            limited.status.apply_headers(response.headers_mut());
+            response
+        }
+        Err(limited) => {
+            tracing::warn!("触发全局限流，建议 {:?} 后重试", limited.retry_after);
+            let mut response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(
+                    header::RETRY_AFTER,
+                    retry_after_secs(limited.retry_after).to_string(),
+                )],
+                Json(ErrorResponse::new(
+                    "rate_limit_error",
+                    ApiMessage::RateLimited.text(locale),
+                )),
+            )
+                .into_response();
+            limited.status.apply_headers(response.headers_mut());
+            response
+        }
+    }
+}
+
+/// 维护模式中间件
+///
+/// 维护模式开启时直接返回 503，错误消息为管理员设置的提示
+pub async fn maintenance_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    match state.maintenance.active_message() {
+        Some(message) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse::new("overloaded_error", message)),
+        )
+            .into_response(),
+        None => next.run(request).await,
+    }
+}
+
+/// 请求头清理中间件
+///
+/// 在认证之后移除配置的敏感请求头，后续的处理、转发和日志都不会再看到它们
+pub async fn strip_request_headers_middleware(
+    State(state): State<AppState>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    for name in state.strip_request_headers.iter() {
+        request.headers_mut().remove(name);
+    }
+    next.run(request).await
+}
+
+/// 自定义响应头中间件
+///
+/// 只补充响应中尚不存在的头，不会覆盖网关自身设置的头
+pub async fn extra_headers_middleware(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    apply_extra_headers(response.headers_mut(), &state.extra_response_headers);
+    response
+}
+
+fn apply_extra_headers(headers: &mut HeaderMap, extra: &[(HeaderName, HeaderValue)]) {
+    for (name, value) in extra {
+        if !headers.contains_key(name) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+/// JSON 美化输出中间件
+///
+/// GET 请求带 `?pretty=true`（或 `?pretty`、`?pretty=1`）时，将 JSON 响应重新格式化为缩进形式，
+/// 方便直接用 curl 查看；默认保持紧凑输出
+pub async fn pretty_json_middleware(request: Request<Body>, next: Next) -> Response {
+    let pretty = request.method() == axum::http::Method::GET
+        && request.uri().query().is_some_and(wants_pretty);
+    let response = next.run(request).await;
+    if !pretty || !is_json_response(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, usize::MAX).await else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, parts.headers).into_response();
+    };
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+    {
+        Ok(pretty) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(pretty)
+        }
+        Err(_) => Body::from(bytes),
+    };
+    Response::from_parts(parts, body)
+}
+
+fn wants_pretty(query: &str) -> bool {
+    query.split('&').any(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+        key == "pretty" && matches!(value, "true" | "1")
+    })
+}
+
+fn is_json_response(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
 /// CORS 中间件层
 ///
 /// **安全说明**：当前配置允许所有来源（Any），这是为了支持公开 API 服务。
@@ -133,3 +530,354 @@ pub fn cors_layer() -> tower_http::cors::CorsLayer {
         .allow_methods(Any)
         .allow_headers(Any)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_extra_headers_does_not_override() {
+        let extra = vec![
+            (
+                HeaderName::from_static("x-served-by"),
+                HeaderValue::from_static("edge-1"),
+            ),
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain"),
+            ),
+        ];
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        apply_extra_headers(&mut headers, &extra);
+
+        assert_eq!(headers.get("x-served-by").unwrap(), "edge-1");
+        assert_eq!(headers.get(header::CONTENT_TYPE).unwrap(), "application/json");
+    }
+
+    #[tokio::test]
+    async fn test_extra_headers_on_v1_response() {
+        use crate::anthropic::create_router_with_provider;
+        use crate::model::config::Config;
+
+        let config = Config {
+            extra_response_headers: HashMap::from([(
+                "x-served-by".to_string(),
+                "edge-1".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let app = create_router_with_provider(&config, "test-key", None, None, None);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let response = reqwest::Client::new()
+            .get(format!("http://{}/v1/models", addr))
+            .header("x-api-key", "test-key")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers().get("x-served-by").unwrap(), "edge-1");
+
+        // 认证失败的响应同样带有自定义头
+        let response = reqwest::Client::new()
+            .get(format!("http://{}/v1/models", addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+        assert_eq!(response.headers().get("x-served-by").unwrap(), "edge-1");
+    }
+
+    #[tokio::test]
+    async fn test_zero_global_rate_limit_blocks_requests() {
+        use crate::anthropic::create_router_with_provider;
+        use crate::model::config::Config;
+
+        let status_for = |global_rate_limit| async move {
+            let config = Config {
+                global_rate_limit,
+                ..Default::default()
+            };
+            let app = create_router_with_provider(&config, "test-key", None, None, None);
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                axum::serve(listener, app).await.unwrap();
+            });
+
+            let mut statuses = Vec::new();
+            for _ in 0..3 {
+                let response = reqwest::Client::new()
+                    .get(format!("http://{}/v1/models", addr))
+                    .header("x-api-key", "test-key")
+                    .send()
+                    .await
+                    .unwrap();
+                statuses.push(response.status().as_u16());
+            }
+            statuses
+        };
+
+        assert_eq!(status_for(Some(0)).await, vec![429, 429, 429]);
+        assert_eq!(status_for(None).await, vec![200, 200, 200]);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_error_is_localized() {
+        let state = AppState::new("test-key").with_global_rate_limit(0);
+        let app = axum::Router::new()
+            .route("/v1/models", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(state, rate_limit_middleware));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let message = |accept_language: &'static str| async move {
+            let response = reqwest::Client::new()
+                .get(format!("http://{}/v1/models", addr))
+                .header("accept-language", accept_language)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 429);
+            let body: serde_json::Value = response.json().await.unwrap();
+            body["error"]["message"].as_str().unwrap().to_string()
+        };
+
+        assert_eq!(message("zh-CN").await, "网关已暂时拒绝所有请求");
+        assert_eq!(message("en").await, "Requests are temporarily blocked by the gateway");
+    }
+
+    const TEST_ADMIN_KEY: &str = "test-admin-key";
+
+    fn admin_config() -> crate::model::config::Config {
+        crate::model::config::Config {
+            admin_api_key: Some(TEST_ADMIN_KEY.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pretty_json_on_admin_get() {
+        use crate::anthropic::create_router_with_provider;
+
+        let app = create_router_with_provider(&admin_config(), "test-key", None, None, None);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let get = |query: &'static str| {
+            reqwest::Client::new()
+                .get(format!("http://{}/admin/maintenance{}", addr, query))
+                .header("x-api-key", TEST_ADMIN_KEY)
+                .send()
+        };
+
+        let compact = get("").await.unwrap().text().await.unwrap();
+        assert!(!compact.contains('\n'));
+
+        let pretty = get("?pretty=true").await.unwrap().text().await.unwrap();
+        assert!(pretty.contains("\n  \"enabled\": false"));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&pretty).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&compact).unwrap()
+        );
+
+        assert!(!get("?pretty=false").await.unwrap().text().await.unwrap().contains('\n'));
+    }
+
+    #[test]
+    fn test_wants_pretty() {
+        assert!(wants_pretty("pretty=true"));
+        assert!(wants_pretty("a=b&pretty"));
+        assert!(wants_pretty("pretty=1"));
+        assert!(!wants_pretty("pretty=false"));
+        assert!(!wants_pretty("prettyish=true"));
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode_toggle() {
+        use crate::anthropic::create_router_with_provider;
+        use serde_json::{json, Value};
+
+        let app = create_router_with_provider(&admin_config(), "test-key", None, None, None);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let get_models = || {
+            client
+                .get(format!("http://{}/v1/models", addr))
+                .header("x-api-key", "test-key")
+                .send()
+        };
+        let set_maintenance = |body: Value| {
+            client
+                .post(format!("http://{}/admin/maintenance", addr))
+                .header("x-api-key", TEST_ADMIN_KEY)
+                .json(&body)
+                .send()
+        };
+
+        assert_eq!(get_models().await.unwrap().status(), 200);
+
+        let response = set_maintenance(json!({"enabled": true, "message": "Upgrading, back soon"}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.json::<Value>().await.unwrap()["enabled"], true);
+
+        let response = get_models().await.unwrap();
+        assert_eq!(response.status(), 503);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "overloaded_error");
+        assert_eq!(body["error"]["message"], "Upgrading, back soon");
+
+        // 管理端点在维护期间仍然可用
+        let response = client
+            .get(format!("http://{}/admin/maintenance", addr))
+            .header("x-api-key", TEST_ADMIN_KEY)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.json::<Value>().await.unwrap()["enabled"], true);
+
+        set_maintenance(json!({"enabled": false})).await.unwrap();
+        assert_eq!(get_models().await.unwrap().status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_requires_auth() {
+        use crate::anthropic::create_router_with_provider;
+
+        let app = create_router_with_provider(&admin_config(), "test-key", None, None, None);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let set_maintenance = |key: Option<&'static str>| {
+            let request = reqwest::Client::new()
+                .post(format!("http://{}/admin/maintenance", addr))
+                .json(&serde_json::json!({"enabled": true}));
+            match key {
+                Some(key) => request.header("x-api-key", key),
+                None => request,
+            }
+            .send()
+        };
+        assert_eq!(set_maintenance(None).await.unwrap().status(), 401);
+        // 普通 API Key 不能访问管理端点
+        assert_eq!(set_maintenance(Some("test-key")).await.unwrap().status(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_stripped_headers_not_forwarded() {
+        use axum::{middleware, routing::get, Router};
+
+        let state = AppState::new("test-key")
+            .with_strip_request_headers(&["cookie".to_string(), "authorization".to_string()]);
+        // 回显处理器实际收到的请求头
+        let app = Router::new()
+            .route(
+                "/echo",
+                get(|headers: HeaderMap| async move {
+                    let mut names: Vec<String> =
+                        headers.keys().map(|k| k.as_str().to_string()).collect();
+                    names.sort();
+                    names.join(",")
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                strip_request_headers_middleware,
+            ))
+            .layer(middleware::from_fn_with_state(state.clone(), auth_middleware))
+            .with_state(state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        // Bearer 认证仍然生效，认证头随后被移除
+        let received = reqwest::Client::new()
+            .get(format!("http://{}/echo", addr))
+            .header(header::AUTHORIZATION, "Bearer test-key")
+            .header(header::COOKIE, "session=secret")
+            .header("x-trace-id", "abc")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+
+        assert!(received.contains("x-trace-id"));
+        assert!(!received.contains("cookie"));
+        assert!(!received.contains("authorization"));
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_use_file_sourced_admin_key() {
+        use crate::anthropic::create_router_with_provider;
+        use crate::model::config::Config;
+
+        let dir = std::env::temp_dir().join(format!("kiro-admin-key-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("config.json");
+        let key_path = dir.join("admin_api_key");
+        std::fs::write(&key_path, "admin-secret-key\n").unwrap();
+        std::fs::write(
+            &config_path,
+            serde_json::json!({
+                "apiKey": "test-key",
+                "adminApiKeyFile": key_path,
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let config = Config::load(&config_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let app = create_router_with_provider(&config, "test-key", None, None, None);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let status_with = |key: &'static str| {
+            let url = format!("http://{}/admin/maintenance", addr);
+            async move {
+                reqwest::Client::new()
+                    .get(url)
+                    .header("x-api-key", key)
+                    .send()
+                    .await
+                    .unwrap()
+                    .status()
+            }
+        };
+
+        assert_eq!(status_with("admin-secret-key").await, 200);
+        // 配置了管理密钥后，普通 API Key 不能访问管理端点
+        assert_eq!(status_with("test-key").await, 401);
+    }
+}
@@ -17,10 +17,16 @@
 //! ```
 
 mod converter;
+mod guardrails;
 mod handlers;
+mod i18n;
+mod latency;
 mod middleware;
+mod rate_limit;
+mod response_cache;
 mod router;
 mod stream;
 pub mod types;
+mod validation;
 
-pub use router::create_router_with_provider;
+pub use router::{admin_api_enabled, create_router_with_provider};
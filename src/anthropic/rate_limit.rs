@@ -0,0 +1,216 @@
+//! 全局请求限流
+//!
+//! 基于令牌桶实现网关级别的请求速率上限，用于保护上游。
+//! 桶容量等于每秒请求数（即允许 1 秒的突发），令牌按配置速率匀速补充。
+//! 每个响应都会带上 `x-ratelimit-*` 头，方便客户端自行节流。
+//! 速率为 0 时令牌桶永远为空，用于暂时拒绝所有请求。
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, HeaderValue};
+
+/// 令牌桶当前状态，用于生成 `x-ratelimit-*` 响应头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// 桶容量（每秒请求数）
+    pub limit: u32,
+    /// 当前剩余可用请求数
+    pub remaining: u32,
+    /// 桶补满所需秒数（向上取整）
+    pub reset_secs: u64,
+}
+
+impl RateLimitStatus {
+    /// 写入 `x-ratelimit-limit` / `x-ratelimit-remaining` / `x-ratelimit-reset` 响应头
+    pub fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("x-ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("x-ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("x-ratelimit-reset", HeaderValue::from(self.reset_secs));
+    }
+}
+
+/// 令牌不足时的限流结果
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimited {
+    /// 下一个令牌可用前需要等待的时间
+    pub retry_after: Duration,
+    /// 当前桶状态
+    pub status: RateLimitStatus,
+}
+
+/// 令牌桶
+pub struct TokenBucket {
+    /// 桶容量（同时也是每秒补充的令牌数）
+    capacity: f64,
+    /// 当前状态
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    /// 当前可用令牌数
+    tokens: f64,
+    /// 上次补充时间
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// 创建新的令牌桶，初始为满
+    ///
+    /// # Arguments
+    /// * `rate_per_sec` - 每秒允许的请求数
+    pub fn new(rate_per_sec: u32) -> Self {
+        Self::new_at(rate_per_sec, Instant::now())
+    }
+
+    fn new_at(rate_per_sec: u32, now: Instant) -> Self {
+        let capacity = rate_per_sec as f64;
+        Self {
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: now,
+            }),
+        }
+    }
+
+    /// 速率为 0 时拒绝所有请求
+    pub fn is_blocked(&self) -> bool {
+        self.capacity <= 0.0
+    }
+
+    /// 尝试取出一个令牌
+    ///
+    /// 成功时返回取出后的桶状态；桶为空时返回等待时间和当前状态
+    pub fn try_acquire(&self) -> Result<RateLimitStatus, RateLimited> {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> Result<RateLimitStatus, RateLimited> {
+        let mut state = self.state.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.capacity).min(self.capacity);
+        state.last_refill = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            return Ok(self.status(state.tokens));
+        }
+
+        let retry_after = if self.capacity <= 0.0 {
+            Duration::from_secs(1)
+        } else {
+            Duration::from_secs_f64((1.0 - state.tokens) / self.capacity)
+        };
+        Err(RateLimited {
+            retry_after,
+            status: self.status(state.tokens),
+        })
+    }
+
+    fn status(&self, tokens: f64) -> RateLimitStatus {
+        let reset_secs = if self.capacity <= 0.0 {
+            0
+        } else {
+            ((self.capacity - tokens) / self.capacity).ceil() as u64
+        };
+        RateLimitStatus {
+            limit: self.capacity as u32,
+            remaining: tokens.floor() as u32,
+            reset_secs,
+        }
+    }
+}
+
+/// 将等待时间换算为 `Retry-After` 头的秒数（向上取整，至少 1 秒）
+pub fn retry_after_secs(wait: Duration) -> u64 {
+    (wait.as_secs_f64().ceil() as u64).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_throttles_when_empty() {
+        let start = Instant::now();
+        let bucket = TokenBucket::new_at(3, start);
+
+        for _ in 0..3 {
+            assert!(bucket.try_acquire_at(start).is_ok());
+        }
+
+        let limited = bucket.try_acquire_at(start).unwrap_err();
+        let wait = limited.retry_after;
+        assert!(wait > Duration::ZERO && wait <= Duration::from_secs(1));
+        assert_eq!(retry_after_secs(wait), 1);
+        assert_eq!(limited.status.remaining, 0);
+    }
+
+    #[test]
+    fn test_bucket_recovers_after_refill() {
+        let start = Instant::now();
+        let bucket = TokenBucket::new_at(2, start);
+
+        assert!(bucket.try_acquire_at(start).is_ok());
+        assert!(bucket.try_acquire_at(start).is_ok());
+        assert!(bucket.try_acquire_at(start).is_err());
+
+        // 半秒补充 1 个令牌
+        let later = start + Duration::from_millis(500);
+        assert!(bucket.try_acquire_at(later).is_ok());
+        assert!(bucket.try_acquire_at(later).is_err());
+
+        // 长时间空闲后最多补满到容量
+        let much_later = later + Duration::from_secs(10);
+        assert!(bucket.try_acquire_at(much_later).is_ok());
+        assert!(bucket.try_acquire_at(much_later).is_ok());
+        assert!(bucket.try_acquire_at(much_later).is_err());
+    }
+
+    #[test]
+    fn test_remaining_decrements_across_requests() {
+        let start = Instant::now();
+        let bucket = TokenBucket::new_at(3, start);
+
+        let remaining: Vec<u32> = (0..3)
+            .map(|_| bucket.try_acquire_at(start).unwrap().remaining)
+            .collect();
+        assert_eq!(remaining, vec![2, 1, 0]);
+
+        let status = bucket.try_acquire_at(start).unwrap_err().status;
+        assert_eq!(status.limit, 3);
+        assert_eq!(status.reset_secs, 1);
+    }
+
+    #[test]
+    fn test_zero_rate_always_blocks() {
+        let start = Instant::now();
+        let bucket = TokenBucket::new_at(0, start);
+        assert!(bucket.is_blocked());
+        assert!(!TokenBucket::new_at(1, start).is_blocked());
+
+        // 无论等待多久都不会补充令牌
+        for later in [start, start + Duration::from_secs(3600)] {
+            let limited = bucket.try_acquire_at(later).unwrap_err();
+            assert_eq!(limited.status.limit, 0);
+            assert_eq!(limited.status.remaining, 0);
+        }
+    }
+
+    #[test]
+    fn test_apply_headers() {
+        let status = RateLimitStatus {
+            limit: 10,
+            remaining: 7,
+            reset_secs: 1,
+        };
+        let mut headers = HeaderMap::new();
+        status.apply_headers(&mut headers);
+
+        assert_eq!(headers.get("x-ratelimit-limit").unwrap(), "10");
+        assert_eq!(headers.get("x-ratelimit-remaining").unwrap(), "7");
+        assert_eq!(headers.get("x-ratelimit-reset").unwrap(), "1");
+    }
+}
@@ -0,0 +1,189 @@
+//! 确定性请求响应缓存
+//!
+//! 仅缓存 `temperature` 显式为 0 的非流式请求：相同请求体的重复调用直接返回缓存的响应，
+//! 不再调用上游。未设置 `temperature`（上游默认 1.0）或大于 0 的请求一律不缓存。
+//! 缓存键为规范化请求体（去掉 `temperature` 后按键名排序序列化）的 SHA-256。
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// 最大缓存条目数，满后先清理过期条目，仍然满则不再写入
+const MAX_ENTRIES: usize = 1024;
+
+struct CacheEntry {
+    response: CachedResponse,
+    expires_at: Instant,
+}
+
+/// 缓存的响应
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+    /// 响应体
+    pub body: Value,
+    /// 实际使用的模型（发生模型回退时与请求的模型不同）
+    pub served_model: String,
+}
+
+/// 响应缓存
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// 缓存统计
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl ResponseCache {
+    /// 创建新的缓存
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 查询缓存，过期条目视为未命中并被移除
+    pub fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let hit = match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.response.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+
+        let counter = if hit.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        hit
+    }
+
+    /// 写入缓存
+    pub fn insert(&self, key: String, response: CachedResponse) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, entry| entry.expires_at > now);
+            if entries.len() >= MAX_ENTRIES {
+                return;
+            }
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                response,
+                expires_at: now + self.ttl,
+            },
+        );
+    }
+
+    /// 获取缓存统计
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+}
+
+/// 计算请求的缓存键
+///
+/// 只有非流式且 `temperature` 显式为 0 的请求才可缓存，否则返回 `None`
+pub fn cache_key(body: &Value) -> Option<String> {
+    if body.get("stream").and_then(Value::as_bool).unwrap_or(false) {
+        return None;
+    }
+    if body.get("temperature").and_then(Value::as_f64) != Some(0.0) {
+        return None;
+    }
+
+    // 去掉 temperature（已确定为 0），避免 `0` 与 `0.0` 序列化结果不同
+    let mut normalized = body.clone();
+    normalized.as_object_mut()?.remove("temperature");
+    let bytes = serde_json::to_vec(&normalized).ok()?;
+    Some(hex::encode(Sha256::digest(&bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(temperature: Option<f64>) -> Value {
+        let mut body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+        if let Some(t) = temperature {
+            body["temperature"] = json!(t);
+        }
+        body
+    }
+
+    fn cached(id: &str) -> CachedResponse {
+        CachedResponse {
+            body: json!({"id": id}),
+            served_model: "claude-sonnet-4".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_deterministic_request_hits_cache() {
+        let cache = ResponseCache::new(Duration::from_secs(60));
+        let key = cache_key(&request(Some(0.0))).unwrap();
+
+        assert!(cache.get(&key).is_none());
+        cache.insert(key.clone(), cached("msg_1"));
+
+        // 键顺序不同的相同请求得到相同的缓存键
+        let reordered: Value = serde_json::from_str(
+            r#"{"temperature":0,"messages":[{"content":"Hello","role":"user"}],"max_tokens":1024,"model":"claude-sonnet-4"}"#,
+        )
+        .unwrap();
+        let same_key = cache_key(&reordered).unwrap();
+        let hit = cache.get(&same_key).unwrap();
+        assert_eq!(hit.body["id"], "msg_1");
+        assert_eq!(hit.served_model, "claude-sonnet-4");
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_non_deterministic_requests_bypass_cache() {
+        assert!(cache_key(&request(Some(0.7))).is_none());
+        assert!(cache_key(&request(None)).is_none());
+
+        let mut stream = request(Some(0.0));
+        stream["stream"] = json!(true);
+        assert!(cache_key(&stream).is_none());
+    }
+
+    #[test]
+    fn test_expired_entry_is_a_miss() {
+        let cache = ResponseCache::new(Duration::ZERO);
+        let key = cache_key(&request(Some(0.0))).unwrap();
+        cache.insert(key.clone(), cached("msg_1"));
+
+        assert!(cache.get(&key).is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
@@ -6,11 +6,22 @@ use axum::{
     Router,
 };
 
+use crate::kiro::clock_skew::ClockSkewMonitor;
 use crate::kiro::provider::KiroProvider;
+use crate::model::config::Config;
 
 use super::{
-    handlers::{count_tokens, get_models, post_messages},
-    middleware::{auth_middleware, cors_layer, AppState},
+    guardrails::Guardrails,
+    i18n::Locale,
+    handlers::{
+        count_tokens, debug_decode, get_cache_stats, get_latency_stats, get_machine_id, get_maintenance,
+        get_models, get_throttling_stats, get_version, health_check, post_messages,
+        set_maintenance,
+    },
+    middleware::{
+        admin_auth_middleware, auth_middleware, cors_layer, extra_headers_middleware, maintenance_middleware,
+        pretty_json_middleware, rate_limit_middleware, strip_request_headers_middleware, AppState,
+    },
 };
 
 /// 创建 Anthropic API 路由
@@ -19,42 +30,230 @@ use super::{
 /// - `GET /v1/models` - 获取可用模型列表
 /// - `POST /v1/messages` - 创建消息（对话）
 /// - `POST /v1/messages/count_tokens` - 计算 token 数量
+/// - `GET /stats/latency` - 上游延迟百分位统计
+/// - `GET /stats/cache` - 响应缓存命中统计
+/// - `GET /stats/throttling` - 上游 429 比例
+/// - `GET /healthz` - 健康检查（无需认证）
+/// - `GET /version` - 版本信息（无需认证）
+/// - `GET/POST /admin/maintenance` - 查看/切换维护模式
+/// - `GET /admin/machine-id` - 上游请求当前使用的 machine_id（设备指纹）
+/// - `POST /debug/decode` - 解码原始 Kiro 事件流（调试用）
 ///
 /// # 认证
-/// 所有 `/v1` 路径需要 API Key 认证，支持：
+/// 除 `/healthz` 和 `/version` 外的所有路径需要 API Key 认证，支持：
 /// - `x-api-key` header
 /// - `Authorization: Bearer <token>` header
 ///
+/// `/admin/*` 和 `/debug/*` 只接受 `adminApiKey`；未配置 `adminApiKey`
+/// 或 `enableAdminApi` 为 false 时这些路由不会挂载
+///
+/// 统计和管理端点的 GET 请求支持 `?pretty=true` 输出缩进 JSON
+///
+/// # 限流
+/// 配置 `globalRateLimit` 后，认证通过的 `/v1` 请求共享一个全局令牌桶，
+/// 超出速率时返回 429
+///
+/// # 维护模式
+/// 通过 `POST /admin/maintenance` 开启后，`/v1` 请求返回 503，管理和统计端点不受影响
+///
 /// # 参数
+/// - `config`: 应用配置，用于读取并发限制、全局限流、排队超时、响应缓存、模型回退链、模型默认参数、内容策略、自定义响应头、请求头清理等可选设置
 /// - `api_key`: API 密钥，用于验证客户端请求
 /// - `kiro_provider`: 可选的 KiroProvider，用于调用上游 API
+/// - `clock_skew_monitor`: 可选的时钟偏差监控器，结果通过 `/healthz` 暴露
 
 /// 创建带有 KiroProvider 的 Anthropic API 路由
 pub fn create_router_with_provider(
+    config: &Config,
     api_key: impl Into<String>,
     kiro_provider: Option<KiroProvider>,
     profile_arn: Option<String>,
+    clock_skew_monitor: Option<ClockSkewMonitor>,
 ) -> Router {
-    let mut state = AppState::new(api_key);
+    let mut state = AppState::new(api_key)
+        .with_kiro_version(&config.kiro_version)
+        .with_max_request_timeout(std::time::Duration::from_secs(
+            config.max_request_timeout_secs,
+        ));
+    if let Some(locale) = Locale::parse(&config.error_locale) {
+        state = state.with_default_locale(locale);
+    }
+    if let Some(key) = &config.admin_api_key {
+        state = state.with_admin_api_key(key);
+    }
     if let Some(provider) = kiro_provider {
         state = state.with_kiro_provider(provider);
     }
     if let Some(arn) = profile_arn {
         state = state.with_profile_arn(arn);
     }
+    if let Some(monitor) = clock_skew_monitor {
+        state = state.with_clock_skew_monitor(monitor);
+    }
+    if let Some(max) = config.max_concurrent_streams {
+        state = state.with_max_concurrent_streams(max);
+    }
+    if let Some(rate) = config.global_rate_limit {
+        state = state.with_global_rate_limit(rate);
+    }
+    if !config.model_fallbacks.is_empty() {
+        state = state.with_model_fallbacks(config.model_fallbacks.clone());
+    }
+    if !config.model_defaults.is_empty() {
+        state = state.with_model_defaults(config.model_defaults.clone());
+    }
+    if config.prepend_system_prompt.is_some() || !config.blocked_patterns.is_empty() {
+        state = state.with_guardrails(Guardrails::new(
+            config.prepend_system_prompt.clone(),
+            &config.blocked_patterns,
+        ));
+    }
+    if let Some(ms) = config.queue_timeout_ms {
+        state = state.with_queue_timeout(std::time::Duration::from_millis(ms));
+    }
+    if !config.extra_response_headers.is_empty() {
+        state = state.with_extra_response_headers(&config.extra_response_headers);
+    }
+    if let Some(ttl) = config.response_cache_ttl_secs {
+        state = state.with_response_cache(std::time::Duration::from_secs(ttl));
+    }
+    if !config.strip_request_headers.is_empty() {
+        state = state.with_strip_request_headers(&config.strip_request_headers);
+    }
 
     // 需要认证的 /v1 路由
     let v1_routes = Router::new()
         .route("/models", get(get_models))
         .route("/messages", post(post_messages))
         .route("/messages/count_tokens", post(count_tokens))
+        // 限流层在认证层内侧，未通过认证的请求不消耗令牌
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit_middleware,
+        ))
+        // 认证通过后移除敏感请求头，处理器看不到这些头
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            strip_request_headers_middleware,
+        ))
+        // 维护模式在认证之后、限流之前检查，维护期间的请求不消耗令牌
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            maintenance_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware,
+        ))
+        // 最外层，确保认证失败、限流等响应也带上自定义头
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            extra_headers_middleware,
+        ));
+
+    // 需要认证的统计路由（不受限流影响）
+    let stats_routes = Router::new()
+        .route("/stats/latency", get(get_latency_stats))
+        .route("/stats/cache", get(get_cache_stats))
+        .route("/stats/throttling", get(get_throttling_stats))
+        .layer(middleware::from_fn(pretty_json_middleware))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
         ));
 
-    Router::new()
+    // 需要管理密钥的管理与调试路由（维护模式期间仍然可用）
+    let admin_routes = Router::new()
+        .route(
+            "/admin/maintenance",
+            get(get_maintenance).post(set_maintenance),
+        )
+        .route("/admin/machine-id", get(get_machine_id))
+        .route("/debug/decode", post(debug_decode))
+        .layer(middleware::from_fn(pretty_json_middleware))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            admin_auth_middleware,
+        ));
+
+    let mut router = Router::new()
+        .route("/healthz", get(health_check))
+        .route("/version", get(get_version))
         .nest("/v1", v1_routes)
-        .layer(cors_layer())
-        .with_state(state)
+        .merge(stats_routes);
+    // 关闭管理 API 或未配置独立的管理密钥时完全不挂载管理路由，
+    // 避免持有普通 API Key 的客户端开启维护模式等
+    if admin_api_enabled(config) {
+        router = router.merge(admin_routes);
+    } else if config.enable_admin_api {
+        tracing::warn!("未配置 adminApiKey，管理与调试端点（/admin/*、/debug/*）不会挂载");
+    }
+
+    router.layer(cors_layer()).with_state(state)
+}
+
+/// 管理与调试路由是否挂载：需开启 `enableAdminApi` 且配置了 `adminApiKey`
+pub fn admin_api_enabled(config: &Config) -> bool {
+    config.enable_admin_api && config.admin_api_key.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以 `request_key` 访问各管理端点，返回状态码
+    async fn admin_status(
+        enable_admin_api: bool,
+        admin_api_key: Option<&str>,
+        request_key: &str,
+    ) -> (u16, u16, u16) {
+        let config = Config {
+            enable_admin_api,
+            admin_api_key: admin_api_key.map(str::to_string),
+            ..Default::default()
+        };
+        let app = create_router_with_provider(&config, "test-key", None, None, None);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client = reqwest::Client::new();
+        let maintenance = client
+            .get(format!("http://{}/admin/maintenance", addr))
+            .header("x-api-key", request_key)
+            .send()
+            .await
+            .unwrap();
+        let decode = client
+            .post(format!("http://{}/debug/decode", addr))
+            .header("x-api-key", request_key)
+            .json(&serde_json::json!({"data": ""}))
+            .send()
+            .await
+            .unwrap();
+        let machine_id = client
+            .get(format!("http://{}/admin/machine-id", addr))
+            .header("x-api-key", request_key)
+            .send()
+            .await
+            .unwrap();
+        (
+            maintenance.status().as_u16(),
+            decode.status().as_u16(),
+            machine_id.status().as_u16(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_can_be_disabled() {
+        // 未配置 Provider 时 machine-id 返回 503，但路由已挂载
+        assert_eq!(admin_status(true, Some("test-admin-key"), "test-admin-key").await, (200, 200, 503));
+        // 普通 API Key 不能访问管理端点
+        assert_eq!(admin_status(true, Some("test-admin-key"), "test-key").await, (401, 401, 401));
+        assert_eq!(admin_status(false, Some("test-admin-key"), "test-key").await, (404, 404, 404));
+        // 未配置管理密钥时不挂载，普通 API Key 无法访问
+        assert_eq!(admin_status(true, None, "test-key").await, (404, 404, 404));
+    }
 }
@@ -157,6 +157,39 @@ impl SseEvent {
             serde_json::to_string(&self.data).unwrap_or_default()
         )
     }
+
+    /// 格式化为 NDJSON 行（事件类型已包含在 data 的 `type` 字段中）
+    pub fn to_ndjson_string(&self) -> String {
+        format!("{}\n", serde_json::to_string(&self.data).unwrap_or_default())
+    }
+}
+
+/// 流式响应的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StreamFormat {
+    /// Server-Sent Events（默认）
+    #[default]
+    Sse,
+    /// 每行一个 JSON 事件对象，供无法解析 SSE 的简单客户端使用
+    Ndjson,
+}
+
+impl StreamFormat {
+    /// 响应的 Content-Type
+    pub fn content_type(self) -> &'static str {
+        match self {
+            StreamFormat::Sse => "text/event-stream",
+            StreamFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// 按当前格式编码一个事件
+    pub fn encode(self, event: &SseEvent) -> String {
+        match self {
+            StreamFormat::Sse => event.to_sse_string(),
+            StreamFormat::Ndjson => event.to_ndjson_string(),
+        }
+    }
 }
 
 /// 内容块状态
@@ -898,6 +931,16 @@ mod tests {
         assert!(sse_str.ends_with("\n\n"));
     }
 
+    #[test]
+    fn test_ndjson_event_format() {
+        let event = SseEvent::new("message_start", json!({"type": "message_start"}));
+        let line = StreamFormat::Ndjson.encode(&event);
+
+        assert_eq!(line, "{\"type\":\"message_start\"}\n");
+        assert_eq!(StreamFormat::Sse.encode(&event), event.to_sse_string());
+        assert_eq!(StreamFormat::Ndjson.content_type(), "application/x-ndjson");
+    }
+
     #[test]
     fn test_sse_state_manager_message_start() {
         let mut manager = SseStateManager::new();
@@ -932,6 +975,41 @@ mod tests {
         assert!(event.is_none());
     }
 
+    #[test]
+    fn test_tool_use_deltas_reassemble_to_valid_json() {
+        let mut ctx = StreamContext::new_with_thinking("test-model", 1, false);
+        let mut events = ctx.generate_initial_events();
+
+        for (input, stop) in [("{\"city\": ", false), ("\"Paris\"}", false), ("", true)] {
+            events.extend(ctx.process_tool_use(&crate::kiro::model::events::ToolUseEvent {
+                name: "get_weather".to_string(),
+                tool_use_id: "tooluse_1".to_string(),
+                input: input.to_string(),
+                stop,
+            }));
+        }
+        events.extend(ctx.generate_final_events());
+
+        let starts: Vec<_> = events
+            .iter()
+            .filter(|e| e.event == "content_block_start" && e.data["content_block"]["type"] == "tool_use")
+            .collect();
+        assert_eq!(starts.len(), 1);
+        let index = &starts[0].data["index"];
+        assert_eq!(starts[0].data["content_block"]["id"], "tooluse_1");
+
+        let partial_json: String = events
+            .iter()
+            .filter(|e| e.event == "content_block_delta" && &e.data["index"] == index)
+            .map(|e| e.data["delta"]["partial_json"].as_str().unwrap())
+            .collect();
+        let input: serde_json::Value = serde_json::from_str(&partial_json).unwrap();
+        assert_eq!(input, json!({"city": "Paris"}));
+
+        let message_delta = events.iter().find(|e| e.event == "message_delta").unwrap();
+        assert_eq!(message_delta.data["delta"]["stop_reason"], "tool_use");
+    }
+
     #[test]
     fn test_text_delta_after_tool_use_restarts_text_block() {
         let mut ctx = StreamContext::new_with_thinking("test-model", 1, false);
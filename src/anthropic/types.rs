@@ -36,6 +36,46 @@ impl ErrorResponse {
     }
 }
 
+// === Version 端点类型 ===
+
+/// 版本信息响应
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionResponse {
+    /// 网关版本（crate 版本）
+    pub version: &'static str,
+    /// 构建时的 git 提交（构建环境无 git 时为 null）
+    pub git_commit: Option<&'static str>,
+    /// 上报给上游的 Kiro 版本
+    pub kiro_version: String,
+}
+
+// === Admin 端点类型 ===
+
+/// 维护模式切换请求
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    /// 是否开启维护模式
+    pub enabled: bool,
+    /// 维护期间返回给客户端的提示（可选）
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// 维护模式状态
+#[derive(Debug, Serialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    pub message: String,
+}
+
+/// 事件流解码调试请求
+#[derive(Debug, Deserialize)]
+pub struct DebugDecodeRequest {
+    /// base64 编码的原始 Kiro 事件流字节
+    pub data: String,
+}
+
 // === Models 端点类型 ===
 
 /// 模型信息
@@ -83,7 +123,29 @@ where
     Ok(value.min(MAX_BUDGET_TOKENS))
 }
 
+/// 反序列化 `system` 字段
+///
+/// Anthropic API 允许 `system` 为字符串或文本块数组，这里统一转换为数组形式
+fn deserialize_system<'de, D>(deserializer: D) -> Result<Option<Vec<SystemMessage>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum SystemField {
+        Text(String),
+        Blocks(Vec<SystemMessage>),
+    }
+
+    Ok(Option::<SystemField>::deserialize(deserializer)?.map(|field| match field {
+        SystemField::Text(text) => vec![SystemMessage { text }],
+        SystemField::Blocks(blocks) => blocks,
+    }))
+}
+
 /// Messages 请求体
+///
+/// 注意：`max_tokens` 仅用于日志和校验，Kiro API 没有对应的输出长度字段，不会透传到上游
 #[derive(Debug, Deserialize)]
 pub struct MessagesRequest {
     pub model: String,
@@ -91,12 +153,20 @@ pub struct MessagesRequest {
     pub messages: Vec<Message>,
     #[serde(default)]
     pub stream: bool,
+    #[serde(default, deserialize_with = "deserialize_system")]
     pub system: Option<Vec<SystemMessage>>,
     pub tools: Option<Vec<Tool>>,
     pub tool_choice: Option<serde_json::Value>,
     pub thinking: Option<Thinking>,
 }
 
+/// Messages 请求的查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct MessagesQuery {
+    /// 流式输出格式：`sse`（默认）或 `ndjson`
+    pub format: Option<String>,
+}
+
 /// 消息
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Message {
@@ -160,7 +230,11 @@ pub struct ImageSource {
 pub struct CountTokensRequest {
     pub model: String,
     pub messages: Vec<Message>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_system",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub system: Option<Vec<SystemMessage>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
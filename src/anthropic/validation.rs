@@ -0,0 +1,189 @@
+//! Anthropic 请求校验
+//!
+//! 在反序列化和协议转换之前对 `/v1/messages` 请求体做轻量校验，
+//! 以便返回精确到字段的 400 错误，而不是在 Kiro 请求构建阶段报出含糊的错误。
+
+use serde_json::Value;
+
+use super::i18n::{ApiMessage, Locale};
+
+/// 允许的消息角色
+const VALID_ROLES: &[&str] = &["user", "assistant"];
+
+/// 校验错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// 出错的字段路径（如 `messages.1.role`）
+    pub field: String,
+    /// 错误描述
+    pub message: ApiMessage,
+}
+
+impl ValidationError {
+    fn new(field: impl Into<String>, message: ApiMessage) -> Self {
+        Self {
+            field: field.into(),
+            message,
+        }
+    }
+
+    /// 按语言输出错误信息（字段路径在前）
+    pub fn text(&self, locale: Locale) -> String {
+        format!("{}: {}", self.field, self.message.text(locale))
+    }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text(Locale::En))
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// 校验 Messages 请求体
+///
+/// 检查项：
+/// - `model` 必须存在且为非空字符串
+/// - `max_tokens` 必须存在且为正整数
+/// - `messages` 必须存在且为非空数组
+/// - 每条消息的 `role` 必须为 `user` 或 `assistant`，且必须包含 `content`
+pub fn validate_messages_request(body: &Value) -> Result<(), ValidationError> {
+    let Some(obj) = body.as_object() else {
+        return Err(ValidationError::new("body", ApiMessage::BodyNotObject));
+    };
+
+    match obj.get("model") {
+        None => return Err(ValidationError::new("model", ApiMessage::FieldRequired)),
+        Some(Value::String(model)) if model.trim().is_empty() => {
+            return Err(ValidationError::new("model", ApiMessage::FieldEmpty));
+        }
+        Some(Value::String(_)) => {}
+        Some(_) => return Err(ValidationError::new("model", ApiMessage::ExpectedString)),
+    }
+
+    match obj.get("max_tokens") {
+        None => return Err(ValidationError::new("max_tokens", ApiMessage::FieldRequired)),
+        Some(v) if v.as_i64().is_some_and(|n| n > 0) => {}
+        Some(_) => {
+            return Err(ValidationError::new(
+                "max_tokens",
+                ApiMessage::ExpectedPositiveInteger,
+            ));
+        }
+    }
+
+    let messages = match obj.get("messages") {
+        None => return Err(ValidationError::new("messages", ApiMessage::FieldRequired)),
+        Some(Value::Array(messages)) => messages,
+        Some(_) => return Err(ValidationError::new("messages", ApiMessage::ExpectedList)),
+    };
+
+    if messages.is_empty() {
+        return Err(ValidationError::new("messages", ApiMessage::MessagesRequired));
+    }
+
+    for (i, message) in messages.iter().enumerate() {
+        let Some(message) = message.as_object() else {
+            return Err(ValidationError::new(
+                format!("messages.{}", i),
+                ApiMessage::ExpectedObject,
+            ));
+        };
+
+        match message.get("role").and_then(|v| v.as_str()) {
+            Some(role) if VALID_ROLES.contains(&role) => {}
+            Some(role) => {
+                return Err(ValidationError::new(
+                    format!("messages.{}.role", i),
+                    ApiMessage::InvalidRole(role.to_string()),
+                ));
+            }
+            None => {
+                return Err(ValidationError::new(
+                    format!("messages.{}.role", i),
+                    ApiMessage::FieldRequired,
+                ));
+            }
+        }
+
+        if !message.contains_key("content") {
+            return Err(ValidationError::new(
+                format!("messages.{}.content", i),
+                ApiMessage::FieldRequired,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_valid_request() {
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "messages": [
+                {"role": "user", "content": "Hello"},
+                {"role": "assistant", "content": [{"type": "text", "text": "Hi"}]}
+            ]
+        });
+        assert!(validate_messages_request(&body).is_ok());
+    }
+
+    #[test]
+    fn test_missing_model() {
+        let body = json!({
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+        let err = validate_messages_request(&body).unwrap_err();
+        assert_eq!(err.field, "model");
+        assert_eq!(err.to_string(), "model: Field required");
+    }
+
+    #[test]
+    fn test_empty_messages() {
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "messages": []
+        });
+        let err = validate_messages_request(&body).unwrap_err();
+        assert_eq!(err.field, "messages");
+        assert_eq!(err.message.text(Locale::En), "At least one message is required");
+        assert_eq!(err.text(Locale::Zh), "messages: 至少需要一条消息");
+    }
+
+    #[test]
+    fn test_invalid_role() {
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 1024,
+            "messages": [
+                {"role": "user", "content": "Hello"},
+                {"role": "system", "content": "Be nice"}
+            ]
+        });
+        let err = validate_messages_request(&body).unwrap_err();
+        assert_eq!(err.field, "messages.1.role");
+        assert!(err.to_string().contains("'system'"));
+        assert!(err.text(Locale::Zh).contains("'system'"));
+    }
+
+    #[test]
+    fn test_invalid_max_tokens() {
+        let body = json!({
+            "model": "claude-sonnet-4",
+            "max_tokens": 0,
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+        let err = validate_messages_request(&body).unwrap_err();
+        assert_eq!(err.field, "max_tokens");
+    }
+}
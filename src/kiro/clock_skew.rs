@@ -0,0 +1,226 @@
+//! 时钟偏差检测
+//!
+//! 通过比较本地时间与上游响应的 `Date` 头检测本机时钟偏差。
+//! 本机时钟不准会导致 Token 过期判断失真（不停刷新或永不刷新），
+//! 这里只做诊断：超过阈值时打印警告，并通过 `/healthz` 暴露最近一次的检测结果。
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::kiro::http_client::{build_client, TlsOptions};
+
+/// 周期检测间隔（1 小时）
+const CHECK_INTERVAL_SECS: u64 = 3600;
+
+/// 探测请求超时时间
+const PROBE_TIMEOUT_SECS: u64 = 10;
+
+/// 单次时钟偏差检测结果
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockSkewSample {
+    /// 偏差秒数（本地时间 - 服务器时间，正数表示本机时钟偏快）
+    pub skew_secs: i64,
+    /// 是否超过告警阈值
+    pub exceeds_threshold: bool,
+    /// 检测时间
+    pub checked_at: DateTime<Utc>,
+}
+
+/// 时钟偏差监控器
+///
+/// 可廉价克隆，内部共享最近一次的检测结果
+#[derive(Clone)]
+pub struct ClockSkewMonitor {
+    /// 告警阈值（秒）
+    threshold_secs: i64,
+    /// 最近一次检测结果
+    last_sample: Arc<RwLock<Option<ClockSkewSample>>>,
+}
+
+impl ClockSkewMonitor {
+    /// 创建新的监控器
+    pub fn new(threshold_secs: u64) -> Self {
+        Self {
+            threshold_secs: threshold_secs as i64,
+            last_sample: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// 获取最近一次检测结果
+    pub fn last_sample(&self) -> Option<ClockSkewSample> {
+        *self.last_sample.read().unwrap()
+    }
+
+    /// 根据服务器 `Date` 头记录一次检测结果
+    ///
+    /// 返回 `None` 表示 `Date` 头无法解析
+    fn record(&self, local: DateTime<Utc>, server_date: &str) -> Option<ClockSkewSample> {
+        let skew = compute_skew(local, server_date)?;
+        let sample = ClockSkewSample {
+            skew_secs: skew.num_seconds(),
+            exceeds_threshold: skew.num_seconds().abs() > self.threshold_secs,
+            checked_at: local,
+        };
+
+        if sample.exceeds_threshold {
+            tracing::warn!(
+                "检测到本机时钟偏差 {} 秒（阈值 {} 秒），Token 过期判断可能失真，请校准系统时间",
+                sample.skew_secs,
+                self.threshold_secs
+            );
+        } else {
+            tracing::debug!("时钟偏差检测: {} 秒", sample.skew_secs);
+        }
+
+        *self.last_sample.write().unwrap() = Some(sample);
+        Some(sample)
+    }
+
+    /// 请求上游地址并根据响应的 `Date` 头检测一次
+    async fn check_once(&self, client: &reqwest::Client, probe_url: &str) -> anyhow::Result<()> {
+        // 任意状态码的响应都带有 Date 头，这里不关心状态码
+        let response = client.get(probe_url).send().await?;
+        let local = Utc::now();
+
+        let server_date = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| anyhow::anyhow!("上游响应缺少 Date 头"))?;
+
+        self.record(local, server_date)
+            .ok_or_else(|| anyhow::anyhow!("无法解析 Date 头: {}", server_date))?;
+        Ok(())
+    }
+
+    /// 启动后台检测任务
+    ///
+    /// 启动时立即检测一次，之后每小时检测一次。探测请求与业务请求一样经由凭证配置的代理发出，
+    /// 并使用相同的 TLS 加固选项
+    ///
+    /// # Arguments
+    /// * `probe_url` - 探测地址
+    /// * `proxy_url` - 代理地址（可选）
+    /// * `tls` - TLS 加固选项
+    pub fn spawn(
+        self,
+        probe_url: String,
+        proxy_url: Option<&str>,
+        tls: &TlsOptions,
+    ) -> anyhow::Result<()> {
+        let client = probe_client(proxy_url, tls)?;
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(StdDuration::from_secs(CHECK_INTERVAL_SECS));
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.check_once(&client, &probe_url).await {
+                    tracing::warn!("时钟偏差检测失败: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// 构建探测请求使用的 HTTP 客户端（经由代理、使用 TLS 加固选项）
+fn probe_client(proxy_url: Option<&str>, tls: &TlsOptions) -> anyhow::Result<reqwest::Client> {
+    build_client(proxy_url, Some(StdDuration::from_secs(PROBE_TIMEOUT_SECS)), tls)
+}
+
+/// 计算本地时间与服务器 `Date` 头（RFC 2822 / IMF-fixdate 格式）之间的偏差
+///
+/// 返回值为本地时间减去服务器时间
+pub fn compute_skew(local: DateTime<Utc>, server_date: &str) -> Option<Duration> {
+    let server = DateTime::parse_from_rfc2822(server_date).ok()?;
+    Some(local - server.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_time() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_compute_skew_in_sync() {
+        let skew = compute_skew(local_time(), "Wed, 01 Jan 2025 12:00:00 GMT").unwrap();
+        assert_eq!(skew.num_seconds(), 0);
+    }
+
+    #[test]
+    fn test_compute_skew_local_ahead() {
+        let skew = compute_skew(local_time(), "Wed, 01 Jan 2025 11:55:00 GMT").unwrap();
+        assert_eq!(skew.num_seconds(), 300);
+    }
+
+    #[test]
+    fn test_compute_skew_local_behind() {
+        let skew = compute_skew(local_time(), "Wed, 01 Jan 2025 12:02:00 GMT").unwrap();
+        assert_eq!(skew.num_seconds(), -120);
+    }
+
+    #[test]
+    fn test_compute_skew_invalid_date() {
+        assert!(compute_skew(local_time(), "not a date").is_none());
+    }
+
+    #[test]
+    fn test_monitor_records_threshold_breach() {
+        let monitor = ClockSkewMonitor::new(60);
+        assert!(monitor.last_sample().is_none());
+
+        let sample = monitor
+            .record(local_time(), "Wed, 01 Jan 2025 11:58:00 GMT")
+            .unwrap();
+        assert_eq!(sample.skew_secs, 120);
+        assert!(sample.exceeds_threshold);
+
+        let sample = monitor
+            .record(local_time(), "Wed, 01 Jan 2025 12:00:30 GMT")
+            .unwrap();
+        assert!(!sample.exceeds_threshold);
+        assert_eq!(monitor.last_sample().unwrap().skew_secs, -30);
+    }
+
+    #[tokio::test]
+    async fn test_probe_goes_through_configured_proxy() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // 模拟 HTTP 代理：记录收到的请求行，并返回带 Date 头的响应
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_url = format!("http://{}", listener.local_addr().unwrap());
+        let proxied = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).into_owned();
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nDate: Wed, 01 Jan 2025 12:00:00 GMT\r\nContent-Length: 0\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            request.lines().next().unwrap_or_default().to_string()
+        });
+
+        assert!(probe_client(Some("not a url"), &TlsOptions::default()).is_err());
+        let client = probe_client(Some(&proxy_url), &TlsOptions::default()).unwrap();
+        let monitor = ClockSkewMonitor::new(60);
+        monitor
+            .check_once(&client, "http://upstream.invalid/probe")
+            .await
+            .unwrap();
+
+        assert_eq!(proxied.await.unwrap(), "GET http://upstream.invalid/probe HTTP/1.1");
+        assert!(monitor.last_sample().is_some());
+    }
+}
@@ -0,0 +1,198 @@
+//! HTTP 客户端构建
+//!
+//! 统一构建访问上游所用的 reqwest Client，支持按凭证配置 HTTP/SOCKS 代理，
+//! 以及按配置进行 TLS 加固（仅 HTTPS、最低 TLS 版本、自定义根证书）
+
+use std::time::Duration;
+
+use reqwest::tls::Version;
+use reqwest::{Certificate, Client, Proxy};
+
+use crate::model::config::Config;
+
+/// 上游连接的 TLS 加固选项
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// 只允许 HTTPS 请求
+    pub https_only: bool,
+    /// 最低 TLS 版本
+    pub min_tls_version: Option<Version>,
+    /// 额外信任的根证书（如企业代理的 CA）
+    pub root_certificates: Vec<Certificate>,
+}
+
+impl TlsOptions {
+    /// 从配置构建，`upstreamCaFile` 无法读取或不包含有效证书时返回错误
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let min_tls_version = match config.upstream_min_tls_version.as_deref() {
+            Some(version) => Some(
+                parse_tls_version(version)
+                    .ok_or_else(|| anyhow::anyhow!("不支持的 TLS 版本: {}", version))?,
+            ),
+            None => None,
+        };
+        let root_certificates = match config.upstream_ca_file.as_deref() {
+            Some(path) => load_ca_bundle(path)?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            https_only: config.upstream_https_only,
+            min_tls_version,
+            root_certificates,
+        })
+    }
+}
+
+/// `upstreamMinTlsVersion` 可选的取值及对应的 TLS 版本
+///
+/// reqwest 使用 native-tls 后端，无法强制最低版本为 TLS 1.3，因此不提供 "1.3"
+pub const SUPPORTED_TLS_VERSIONS: &[(&str, Version)] = &[
+    ("1.0", Version::TLS_1_0),
+    ("1.1", Version::TLS_1_1),
+    ("1.2", Version::TLS_1_2),
+];
+
+/// 解析配置中的 TLS 版本，不支持的取值返回 `None`
+pub fn parse_tls_version(version: &str) -> Option<Version> {
+    SUPPORTED_TLS_VERSIONS
+        .iter()
+        .find(|(name, _)| *name == version)
+        .map(|(_, version)| *version)
+}
+
+/// 读取 PEM 格式的 CA 证书包
+fn load_ca_bundle(path: &str) -> anyhow::Result<Vec<Certificate>> {
+    let pem = std::fs::read(path).map_err(|e| anyhow::anyhow!("读取 CA 文件 {} 失败: {}", path, e))?;
+    let certificates = Certificate::from_pem_bundle(&pem)
+        .map_err(|e| anyhow::anyhow!("解析 CA 文件 {} 失败: {}", path, e))?;
+    if certificates.is_empty() {
+        anyhow::bail!("CA 文件 {} 中没有找到 PEM 证书", path);
+    }
+    Ok(certificates)
+}
+
+/// 构建 HTTP 客户端
+///
+/// # Arguments
+/// * `proxy_url` - 代理地址（可选），支持 `http://`、`https://`、`socks5://` 等
+/// * `timeout` - 请求超时时间（可选）
+/// * `tls` - TLS 加固选项
+pub fn build_client(
+    proxy_url: Option<&str>,
+    timeout: Option<Duration>,
+    tls: &TlsOptions,
+) -> anyhow::Result<Client> {
+    let mut builder = Client::builder().https_only(tls.https_only);
+
+    if let Some(version) = tls.min_tls_version {
+        builder = builder.min_tls_version(version);
+    }
+    for certificate in &tls.root_certificates {
+        builder = builder.add_root_certificate(certificate.clone());
+    }
+
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+
+    if let Some(proxy_url) = proxy_url {
+        let proxy = Proxy::all(proxy_url)
+            .map_err(|e| anyhow::anyhow!("无效的代理地址 {}: {}", proxy_url, e))?;
+        builder = builder.proxy(proxy);
+    }
+
+    builder
+        .build()
+        .map_err(|e| anyhow::anyhow!("构建 HTTP 客户端失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_client_with_http_proxy() {
+        let client = build_client(Some("http://127.0.0.1:18080"), None, &TlsOptions::default()).unwrap();
+        assert!(format!("{:?}", client).contains("127.0.0.1:18080"));
+    }
+
+    #[test]
+    fn test_build_client_with_socks_proxy() {
+        let client = build_client(Some("socks5://127.0.0.1:11080"), None, &TlsOptions::default()).unwrap();
+        assert!(format!("{:?}", client).contains("127.0.0.1:11080"));
+    }
+
+    #[test]
+    fn test_build_client_without_proxy() {
+        assert!(build_client(None, Some(Duration::from_secs(5)), &TlsOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_build_client_invalid_proxy() {
+        assert!(build_client(Some("not a url"), None, &TlsOptions::default()).is_err());
+    }
+
+    const TEST_CA_PEM: &str = "-----BEGIN CERTIFICATE-----\nMIIBijCCATGgAwIBAgIUKuwtG51GfQ+kIYKjZoPoZMCnSZ0wCgYIKoZIzj0EAwIw\nGjEYMBYGA1UEAwwPa2lyby1ycyB0ZXN0IENBMCAXDTI2MTAxNjEzMTEyNVoYDzIx\nMjYwOTIyMTMxMTI1WjAaMRgwFgYDVQQDDA9raXJvLXJzIHRlc3QgQ0EwWTATBgcq\nhkjOPQIBBggqhkjOPQMBBwNCAATxw8cH8y5ZWPQy5S5i9hvpyiToSZN95shFhmna\nJApTp0R/vjrvxPhiiIh0dPKDeIbobyuJHvUxjRqmF25kCZ+Go1MwUTAdBgNVHQ4E\nFgQUiAmyEy8fp41pBLjOscrJtT/+3WUwHwYDVR0jBBgwFoAUiAmyEy8fp41pBLjO\nscrJtT/+3WUwDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNHADBEAiAcRS4A\nU2goYCj4EM35OGclnMT1UaGFminP9YKRfG74DQIgFCqYPLbvWz6Kz0VmPjgh96Sn\ndL9ld6vqUWddL+ZVh1g=\n-----END CERTIFICATE-----\n";
+
+    fn write_temp(content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("kiro-ca-test-{}.pem", uuid::Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn config_with_ca(path: &std::path::Path) -> Config {
+        Config {
+            upstream_ca_file: Some(path.to_string_lossy().into_owned()),
+            upstream_https_only: true,
+            upstream_min_tls_version: Some("1.2".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_client_with_ca_bundle() {
+        let path = write_temp(TEST_CA_PEM);
+        let tls = TlsOptions::from_config(&config_with_ca(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tls.root_certificates.len(), 1);
+        assert_eq!(tls.min_tls_version, Some(Version::TLS_1_2));
+        assert!(build_client(None, None, &tls).is_ok());
+    }
+
+    #[test]
+    fn test_bogus_ca_bundle_fails() {
+        let path = write_temp("this is not a certificate");
+        let err = TlsOptions::from_config(&config_with_ca(&path)).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("没有找到 PEM 证书"));
+
+        let missing = std::env::temp_dir().join("kiro-ca-test-missing.pem");
+        assert!(TlsOptions::from_config(&config_with_ca(&missing)).is_err());
+    }
+
+    #[test]
+    fn test_supported_tls_versions_build() {
+        for (name, version) in SUPPORTED_TLS_VERSIONS {
+            assert_eq!(parse_tls_version(name), Some(*version));
+            let tls = TlsOptions {
+                min_tls_version: Some(*version),
+                ..Default::default()
+            };
+            assert!(build_client(None, None, &tls).is_ok(), "TLS {} 应可用", name);
+        }
+        assert_eq!(parse_tls_version("1.3"), None);
+    }
+
+    #[tokio::test]
+    async fn test_https_only_rejects_plain_http() {
+        let tls = TlsOptions {
+            https_only: true,
+            ..Default::default()
+        };
+        let client = build_client(None, None, &tls).unwrap();
+        let err = client.get("http://127.0.0.1:9/").send().await.unwrap_err();
+        assert!(err.is_builder());
+    }
+}
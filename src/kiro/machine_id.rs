@@ -112,4 +112,36 @@ mod tests {
         let result = generate_from_credentials(&credentials, &config);
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_generate_is_stable_for_identical_credentials() {
+        let config = Config::default();
+        let credentials = KiroCredentials {
+            refresh_token: Some("refresh-a".to_string()),
+            ..Default::default()
+        };
+
+        let first = generate_from_credentials(&credentials, &config);
+        let second = generate_from_credentials(&credentials.clone(), &config);
+        assert!(first.is_some());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_generate_differs_for_different_credentials() {
+        let config = Config::default();
+        let a = KiroCredentials {
+            refresh_token: Some("refresh-a".to_string()),
+            ..Default::default()
+        };
+        let b = KiroCredentials {
+            refresh_token: Some("refresh-b".to_string()),
+            ..Default::default()
+        };
+
+        assert_ne!(
+            generate_from_credentials(&a, &config),
+            generate_from_credentials(&b, &config)
+        );
+    }
 }
@@ -1,7 +1,10 @@
 //! Kiro API 客户端模块
 
+pub mod clock_skew;
+pub mod http_client;
 pub mod machine_id;
 pub mod model;
 pub mod parser;
 pub mod provider;
+pub mod throttle;
 pub mod token_manager;
@@ -1,10 +1,11 @@
 //! Kiro OAuth 凭证数据模型
 //!
 //! 支持从 Kiro IDE 的凭证文件加载，使用 Social 认证方式
+//! 凭证文件可以是 JSON、YAML 或 TOML 格式，按扩展名识别
 
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Kiro OAuth 凭证
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -45,6 +46,14 @@ pub struct KiroCredentials {
     /// IdC Start URL (IdC 认证需要)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_url: Option<String>,
+
+    /// 该账号使用的代理地址（可选，支持 HTTP/SOCKS5）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<String>,
+
+    /// 备用区域（可选），主区域返回 5xx 时改用该区域重试
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_region: Option<String>,
 }
 
 impl KiroCredentials {
@@ -60,21 +69,111 @@ impl KiroCredentials {
     }
 
     /// 从文件加载凭证
+    ///
+    /// 按扩展名选择解析格式：`.yaml` / `.yml` 为 YAML，`.toml` 为 TOML，其他为 JSON
     pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
         let content = fs::read_to_string(path.as_ref())?;
         if content.is_empty() {
             anyhow::bail!("凭证文件为空: {:?}", path.as_ref());
         }
-        let credentials = Self::from_json(&content)?;
+        let credentials = match CredentialsFormat::from_path(path.as_ref()) {
+            CredentialsFormat::Json => Self::from_json(&content)?,
+            CredentialsFormat::Yaml => serde_norway::from_str(&content)?,
+            CredentialsFormat::Toml => toml::from_str(&content)?,
+        };
         Ok(credentials)
     }
 
+    /// Kiro IDE 登录后保存的 Token 文件默认路径
+    ///
+    /// 所有平台均为 `<用户目录>/.aws/sso/cache/kiro-auth-token.json`，
+    /// 用户目录在 Windows 上取 `USERPROFILE`，其他平台取 `HOME`
+    pub fn kiro_ide_token_path() -> Option<PathBuf> {
+        let home_var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+        let home = std::env::var_os(home_var)?;
+        Some(
+            PathBuf::from(home)
+                .join(".aws")
+                .join("sso")
+                .join("cache")
+                .join(KIRO_IDE_TOKEN_FILE),
+        )
+    }
+
+    /// 从 Kiro IDE 的 Token 文件导入凭证
+    ///
+    /// IdC 登录时 Token 文件只记录 `clientIdHash`，`clientId` / `clientSecret`
+    /// 保存在同目录下的 `<clientIdHash>.json` 中，这里会一并读取
+    pub fn import_from_kiro_ide(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            anyhow::bail!("未找到 Kiro IDE 凭证文件: {:?}，请先在 Kiro IDE 中登录", path);
+        }
+
+        let content = fs::read_to_string(path)?;
+        let mut credentials = Self::from_json(&content)?;
+
+        if credentials.refresh_token.as_deref().is_none_or(str::is_empty) {
+            anyhow::bail!("Kiro IDE 凭证文件缺少 refreshToken: {:?}", path);
+        }
+
+        let raw: serde_json::Value = serde_json::from_str(&content)?;
+        if let Some(client_id_hash) = raw.get("clientIdHash").and_then(|v| v.as_str())
+            && credentials.client_id.is_none()
+        {
+            let registration_path = path.with_file_name(format!("{}.json", client_id_hash));
+            let registration = fs::read_to_string(&registration_path).map_err(|e| {
+                anyhow::anyhow!("读取 IdC 客户端注册文件失败 {:?}: {}", registration_path, e)
+            })?;
+            let registration = Self::from_json(&registration)?;
+            credentials.client_id = registration.client_id;
+            credentials.client_secret = registration.client_secret;
+        }
+
+        Ok(credentials)
+    }
+
+    /// 按指定格式序列化
+    pub fn serialize_as(&self, format: CredentialsFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            CredentialsFormat::Json => self.to_pretty_json()?,
+            CredentialsFormat::Yaml => serde_norway::to_string(self)?,
+            CredentialsFormat::Toml => toml::to_string(self)?,
+        })
+    }
+
     /// 序列化为格式化的 JSON 字符串
     pub fn to_pretty_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string_pretty(self)
     }
 }
 
+/// Kiro IDE Token 文件名
+const KIRO_IDE_TOKEN_FILE: &str = "kiro-auth-token.json";
+
+/// 凭证文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialsFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl CredentialsFormat {
+    /// 根据文件扩展名识别格式，未知扩展名按 JSON 处理
+    pub fn from_path(path: &Path) -> Self {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("yaml") | Some("yml") => Self::Yaml,
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,6 +221,8 @@ mod tests {
             client_id: None,
             client_secret: None,
             start_url: None,
+            proxy_url: None,
+            fallback_region: None,
         };
 
         let json = creds.to_pretty_json().unwrap();
@@ -130,8 +231,151 @@ mod tests {
         assert!(!json.contains("refreshToken"));
     }
 
+    #[test]
+    fn test_from_json_with_proxy_url() {
+        let json = r#"{
+            "refreshToken": "test_refresh",
+            "proxyUrl": "socks5://127.0.0.1:1080"
+        }"#;
+
+        let creds = KiroCredentials::from_json(json).unwrap();
+        assert_eq!(creds.proxy_url, Some("socks5://127.0.0.1:1080".to_string()));
+    }
+
     #[test]
     fn test_default_credentials_path() {
         assert_eq!(KiroCredentials::default_credentials_path(), "credentials.json");
     }
+
+    fn sample_credentials() -> KiroCredentials {
+        KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("refresh".to_string()),
+            profile_arn: Some("arn:aws:test".to_string()),
+            expires_at: Some("2024-01-01T00:00:00Z".to_string()),
+            auth_method: Some("social".to_string()),
+            proxy_url: Some("socks5://127.0.0.1:1080".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn assert_round_trip(file_name: &str, content: String) {
+        let dir = std::env::temp_dir().join(format!(
+            "kiro-credentials-test-{}",
+            uuid::Uuid::new_v4()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name);
+        fs::write(&path, content).unwrap();
+
+        let loaded = KiroCredentials::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.to_pretty_json().unwrap(), sample_credentials().to_pretty_json().unwrap());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(CredentialsFormat::from_path(Path::new("a.json")), CredentialsFormat::Json);
+        assert_eq!(CredentialsFormat::from_path(Path::new("a.yaml")), CredentialsFormat::Yaml);
+        assert_eq!(CredentialsFormat::from_path(Path::new("a.YML")), CredentialsFormat::Yaml);
+        assert_eq!(CredentialsFormat::from_path(Path::new("a.toml")), CredentialsFormat::Toml);
+        assert_eq!(CredentialsFormat::from_path(Path::new("credentials")), CredentialsFormat::Json);
+    }
+
+    #[test]
+    fn test_load_json_round_trip() {
+        assert_round_trip("credentials.json", sample_credentials().to_pretty_json().unwrap());
+    }
+
+    #[test]
+    fn test_load_yaml_round_trip() {
+        let content = sample_credentials().serialize_as(CredentialsFormat::Yaml).unwrap();
+        assert_round_trip("credentials.yaml", content);
+    }
+
+    #[test]
+    fn test_load_toml_round_trip() {
+        let content = sample_credentials().serialize_as(CredentialsFormat::Toml).unwrap();
+        assert_round_trip("credentials.toml", content);
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kiro-ide-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_import_from_kiro_ide_social() {
+        let dir = temp_dir();
+        let path = dir.join(KIRO_IDE_TOKEN_FILE);
+        fs::write(
+            &path,
+            r#"{
+                "accessToken": "aoa_token",
+                "refreshToken": "aor_refresh",
+                "profileArn": "arn:aws:codewhisperer:us-east-1:123456789:profile/ABC",
+                "expiresAt": "2025-01-01T00:00:00.000Z",
+                "authMethod": "social",
+                "provider": "Google"
+            }"#,
+        )
+        .unwrap();
+
+        let creds = KiroCredentials::import_from_kiro_ide(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(creds.refresh_token.as_deref(), Some("aor_refresh"));
+        assert_eq!(creds.auth_method.as_deref(), Some("social"));
+        assert_eq!(creds.provider.as_deref(), Some("Google"));
+    }
+
+    #[test]
+    fn test_import_from_kiro_ide_idc_reads_client_registration() {
+        let dir = temp_dir();
+        let path = dir.join(KIRO_IDE_TOKEN_FILE);
+        fs::write(
+            &path,
+            r#"{
+                "accessToken": "token",
+                "refreshToken": "refresh",
+                "authMethod": "IdC",
+                "clientIdHash": "abc123"
+            }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("abc123.json"),
+            r#"{"clientId": "client", "clientSecret": "secret"}"#,
+        )
+        .unwrap();
+
+        let creds = KiroCredentials::import_from_kiro_ide(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(creds.client_id.as_deref(), Some("client"));
+        assert_eq!(creds.client_secret.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn test_import_from_kiro_ide_requires_refresh_token() {
+        let dir = temp_dir();
+        let path = dir.join(KIRO_IDE_TOKEN_FILE);
+        fs::write(&path, r#"{"accessToken": "token"}"#).unwrap();
+
+        let result = KiroCredentials::import_from_kiro_ide(&path);
+        let missing = KiroCredentials::import_from_kiro_ide(&dir.join("missing.json"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.unwrap_err().to_string().contains("refreshToken"));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_kiro_ide_token_path() {
+        if let Some(path) = KiroCredentials::kiro_ide_token_path() {
+            assert!(path.ends_with(".aws/sso/cache/kiro-auth-token.json"));
+        }
+    }
 }
@@ -31,7 +31,7 @@
 //! ```
 
 use super::error::{ParseError, ParseResult};
-use super::frame::{Frame, FrameParser};
+use super::frame::{is_valid_prelude, Frame, FrameParser, PRELUDE_SIZE};
 use bytes::{Buf, BytesMut};
 
 /// 默认最大缓冲区大小 (16 MB)
@@ -220,9 +220,13 @@ impl EventStreamDecoder {
                     });
                 }
 
-                // 尝试容错恢复
-                self.try_recover();
-                self.state = DecoderState::Recovering;
+                // 尝试容错恢复：找到下一个消息边界时可立即继续解码，
+                // 否则等待更多数据
+                self.state = if self.try_recover() {
+                    DecoderState::Ready
+                } else {
+                    DecoderState::Recovering
+                };
                 Err(e)
             }
         }
@@ -233,20 +237,31 @@ impl EventStreamDecoder {
         DecodeIter { decoder: self }
     }
 
-    /// 尝试容错恢复
+    /// 尝试容错恢复（重新同步）
     ///
-    /// 策略：跳过一个字节，尝试找到下一个有效消息边界
-    fn try_recover(&mut self) {
-        if !self.buffer.is_empty() {
-            let skipped_byte = self.buffer[0];
-            self.buffer.advance(1);
-            self.bytes_skipped += 1;
-            tracing::warn!(
-                "容错恢复: 跳过字节 0x{:02x} (累计跳过 {} 字节)",
-                skipped_byte,
-                self.bytes_skipped
-            );
+    /// 策略：丢弃当前位置的数据，向后扫描到下一个有效 Prelude（即下一个消息边界）。
+    /// 缓冲区中找不到时只保留末尾可能是不完整 Prelude 的字节，等待更多数据后继续扫描。
+    ///
+    /// # Returns
+    /// 找到下一个消息边界时返回 `true`
+    fn try_recover(&mut self) -> bool {
+        if self.buffer.is_empty() {
+            return false;
         }
+
+        let boundary = (1..self.buffer.len()).find(|&i| is_valid_prelude(&self.buffer[i..]));
+        let skip = boundary
+            .unwrap_or_else(|| self.buffer.len().saturating_sub(PRELUDE_SIZE - 1).max(1));
+
+        self.buffer.advance(skip);
+        self.bytes_skipped += skip;
+        tracing::warn!(
+            "容错恢复: 跳过 {} 字节{} (累计跳过 {} 字节)",
+            skip,
+            if boundary.is_some() { "，已重新同步到下一帧" } else { "" },
+            self.bytes_skipped
+        );
+        boundary.is_some()
     }
 
     // ==================== 生命周期管理方法 ====================
@@ -342,6 +357,7 @@ impl<'a> Iterator for DecodeIter<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::kiro::parser::frame::encode_event_frame;
 
     #[test]
     fn test_decoder_new() {
@@ -408,4 +424,46 @@ mod tests {
         assert!(decoder.is_ready());
         assert_eq!(decoder.error_count(), 0);
     }
+
+    fn decoded_payloads(decoder: &mut EventStreamDecoder) -> Vec<String> {
+        decoder
+            .decode_iter()
+            .filter_map(|r| r.ok())
+            .map(|f| f.payload_as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_decoder_resyncs_after_garbage() {
+        let garbage = [0x00, 0x00, 0x01, 0x00, 0xde, 0xad, 0xbe, 0xef, 0x13, 0x37, 0x42, 0x42, 0x99];
+        let mut data = encode_event_frame("assistantResponseEvent", b"first");
+        data.extend_from_slice(&garbage);
+        data.extend(encode_event_frame("assistantResponseEvent", b"second"));
+        data.extend(encode_event_frame("assistantResponseEvent", b"third"));
+
+        let mut decoder = EventStreamDecoder::new();
+        decoder.feed(&data).unwrap();
+
+        assert_eq!(decoded_payloads(&mut decoder), vec!["first", "second", "third"]);
+        assert_eq!(decoder.bytes_skipped(), garbage.len());
+        assert!(!decoder.is_stopped());
+    }
+
+    #[test]
+    fn test_decoder_resyncs_across_feeds() {
+        let garbage = [0x5a; 40];
+        let mut decoder = EventStreamDecoder::new();
+
+        let mut first = encode_event_frame("assistantResponseEvent", b"first");
+        first.extend_from_slice(&garbage[..20]);
+        decoder.feed(&first).unwrap();
+        assert_eq!(decoded_payloads(&mut decoder), vec!["first"]);
+
+        // 剩余的垃圾数据与下一帧在后续数据块中到达
+        let mut rest = garbage[20..].to_vec();
+        rest.extend(encode_event_frame("assistantResponseEvent", b"second"));
+        decoder.feed(&rest).unwrap();
+        assert_eq!(decoded_payloads(&mut decoder), vec!["second"]);
+        assert_eq!(decoder.bytes_skipped(), garbage.len());
+    }
 }
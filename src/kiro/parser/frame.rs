@@ -94,6 +94,19 @@ impl Frame {
     }
 }
 
+/// 检查缓冲区开头是否为有效的 Prelude（长度在合法范围内且 CRC 匹配）
+///
+/// 用于解码出错后寻找下一个消息边界
+pub fn is_valid_prelude(buffer: &[u8]) -> bool {
+    if buffer.len() < PRELUDE_SIZE {
+        return false;
+    }
+    let total_length = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+    let prelude_crc = u32::from_be_bytes([buffer[8], buffer[9], buffer[10], buffer[11]]);
+    (MIN_MESSAGE_SIZE as u32..=MAX_MESSAGE_SIZE).contains(&total_length)
+        && crc32(&buffer[..8]) == prelude_crc
+}
+
 /// 消息帧解析器
 ///
 /// 采用状态机设计，支持流式解析
@@ -279,15 +292,7 @@ impl FrameParser {
             });
         }
 
-        let total_length = total_length as usize;
-        let header_length = header_length as usize;
-
-        // 检查是否有完整的消息
-        if buffer.len() < total_length {
-            return Ok(None);
-        }
-
-        // 验证 Prelude CRC
+        // 验证 Prelude CRC（在等待完整消息之前，避免损坏的长度字段让解析一直等待）
         let actual_prelude_crc = crc32(&buffer[..8]);
         if actual_prelude_crc != prelude_crc {
             return Err(ParseError::PreludeCrcMismatch {
@@ -296,6 +301,14 @@ impl FrameParser {
             });
         }
 
+        let total_length = total_length as usize;
+        let header_length = header_length as usize;
+
+        // 检查是否有完整的消息
+        if buffer.len() < total_length {
+            return Ok(None);
+        }
+
         // 读取 Message CRC
         let message_crc = u32::from_be_bytes([
             buffer[total_length - 4],
@@ -335,6 +348,27 @@ impl FrameParser {
     }
 }
 
+/// 构造一个带 `:event-type` 头的有效消息帧（测试用）
+#[cfg(test)]
+pub(crate) fn encode_event_frame(event_type: &str, payload: &[u8]) -> Vec<u8> {
+    let name = b":event-type";
+    let mut headers = vec![name.len() as u8];
+    headers.extend_from_slice(name);
+    headers.push(7); // String
+    headers.extend_from_slice(&(event_type.len() as u16).to_be_bytes());
+    headers.extend_from_slice(event_type.as_bytes());
+
+    let total_length = (PRELUDE_SIZE + headers.len() + payload.len() + 4) as u32;
+    let mut frame = Vec::new();
+    frame.extend_from_slice(&total_length.to_be_bytes());
+    frame.extend_from_slice(&(headers.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&crc32(&frame).to_be_bytes());
+    frame.extend_from_slice(&headers);
+    frame.extend_from_slice(payload);
+    frame.extend_from_slice(&crc32(&frame).to_be_bytes());
+    frame
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,12 +3,19 @@
 //! 核心组件，负责与 Kiro API 通信
 //! 支持流式和非流式请求
 
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONNECTION, CONTENT_TYPE, HOST};
 use reqwest::Client;
 use uuid::Uuid;
 
+use crate::kiro::http_client::{build_client, TlsOptions};
 use crate::kiro::machine_id;
+use crate::kiro::throttle::ThrottleTracker;
 use crate::kiro::token_manager::TokenManager;
+use crate::model::config::is_valid_region;
 
 /// Kiro API Provider
 ///
@@ -16,39 +23,160 @@ use crate::kiro::token_manager::TokenManager;
 pub struct KiroProvider {
     token_manager: TokenManager,
     client: Client,
+    /// 按区域缓存的 API 基础域名
+    domain_cache: Mutex<HashMap<String, String>>,
+    /// 上游 429 比例统计
+    throttle_tracker: Arc<ThrottleTracker>,
+    /// 最近一次发送请求时使用的 machine_id（与 AppState 共享）
+    machine_id: Arc<RwLock<Option<String>>>,
 }
 
 impl KiroProvider {
     /// 创建新的 KiroProvider 实例
-    pub fn new(token_manager: TokenManager) -> Self {
-        Self {
+    ///
+    /// 如果凭证配置了 `proxyUrl`，所有上游请求都会经由该代理发出；
+    /// 配置的 TLS 加固选项（如自定义 CA 文件）无效时返回错误
+    pub fn new(token_manager: TokenManager) -> anyhow::Result<Self> {
+        let tls = TlsOptions::from_config(token_manager.config())?;
+        let token_manager = token_manager.with_tls_options(tls.clone());
+        let throttle_window =
+            Duration::from_secs(token_manager.config().throttle_window_secs);
+        let client = build_client(
+            token_manager.credentials().proxy_url.as_deref(),
+            Some(Duration::from_secs(720)), // 12 分钟超时
+            &tls,
+        )?;
+        let machine_id = machine_id::generate_from_credentials(
+            token_manager.credentials(),
+            token_manager.config(),
+        );
+
+        Ok(Self {
             token_manager,
-            client: Client::builder()
-                .timeout(std::time::Duration::from_secs(720)) // 12 分钟超时
-                .build()
-                .expect("Failed to create HTTP client"),
-        }
+            client,
+            domain_cache: Mutex::new(HashMap::new()),
+            throttle_tracker: Arc::new(ThrottleTracker::new(throttle_window)),
+            machine_id: Arc::new(RwLock::new(machine_id)),
+        })
+    }
+
+    /// 获取上游 429 比例统计器（与 Provider 共享）
+    pub fn throttle_tracker(&self) -> Arc<ThrottleTracker> {
+        self.throttle_tracker.clone()
+    }
+
+    /// 获取当前 machine_id 的共享句柄（每次刷新 Token 后更新，读取时无需等待 Provider 锁）
+    pub fn machine_id_handle(&self) -> Arc<RwLock<Option<String>>> {
+        self.machine_id.clone()
+    }
+
+    /// 获取上游连接使用的 TLS 加固选项
+    pub fn tls_options(&self) -> &TlsOptions {
+        self.token_manager.tls_options()
     }
 
-    /// 获取 API 基础 URL
+    /// 获取 API 基础 URL（主区域）
     pub fn base_url(&self) -> String {
-        format!(
-            "https://q.{}.amazonaws.com/generateAssistantResponse",
-            self.token_manager.config().region
+        self.base_url_for_region(&self.token_manager.config().region)
+    }
+
+    /// 获取指定区域的 API 基础 URL
+    ///
+    /// 配置了 `upstreamBaseOverride` 时使用该地址，其中的 `{region}` 会被替换为区域
+    fn base_url_for_region(&self, region: &str) -> String {
+        match &self.token_manager.config().upstream_base_override {
+            Some(template) => template.replace("{region}", region),
+            None => format!(
+                "https://{}/generateAssistantResponse",
+                default_domain_for_region(region)
+            ),
+        }
+    }
+
+    /// 获取指定区域的 API 基础域名（用于 Host 头，与实际请求的 URL 保持一致）
+    ///
+    /// 区域格式无效时直接返回错误，避免请求发往错误的主机；计算结果按区域缓存
+    fn base_domain_for_region(&self, region: &str) -> anyhow::Result<String> {
+        if !is_valid_region(region) {
+            anyhow::bail!("无效的区域: {:?}（示例: us-east-1）", region);
+        }
+
+        let mut cache = self.domain_cache.lock().unwrap();
+        if let Some(domain) = cache.get(region) {
+            return Ok(domain.clone());
+        }
+
+        let domain = self.compute_base_domain(region);
+        cache.insert(region.to_string(), domain.clone());
+        Ok(domain)
+    }
+
+    fn compute_base_domain(&self, region: &str) -> String {
+        if self.token_manager.config().upstream_base_override.is_some()
+            && let Ok(url) = reqwest::Url::parse(&self.base_url_for_region(region))
+            && let Some(host) = url.host_str()
+        {
+            return match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host.to_string(),
+            };
+        }
+        default_domain_for_region(region)
+    }
+
+    /// 按顺序返回可尝试的区域：主区域，以及凭证中配置的备用区域（如有）
+    fn candidate_regions(&self) -> Vec<String> {
+        let primary = self.token_manager.config().region.clone();
+        let mut regions = vec![primary.clone()];
+        if let Some(fallback) = &self.token_manager.credentials().fallback_region
+            && *fallback != primary
+        {
+            regions.push(fallback.clone());
+        }
+        regions
+    }
+
+    /// 获取当前凭证派生的 machine_id（上游设备指纹）
+    pub fn machine_id(&self) -> Option<String> {
+        machine_id::generate_from_credentials(
+            self.token_manager.credentials(),
+            self.token_manager.config(),
         )
     }
 
-    /// 获取 API 基础域名
-    pub fn base_domain(&self) -> String {
-        format!("q.{}.amazonaws.com", self.token_manager.config().region)
+    /// 按当前凭证更新共享的 machine_id
+    ///
+    /// 未配置 profileArn 时 machine_id 由 refreshToken 派生，刷新 Token 轮换 refreshToken 后会随之变化
+    fn sync_machine_id(&self) {
+        let current = self.machine_id();
+        let mut shared = self.machine_id.write().unwrap();
+        if *shared != current {
+            tracing::warn!(
+                "machine_id 已变化: {} -> {}",
+                shared.as_deref().unwrap_or("-"),
+                current.as_deref().unwrap_or("-")
+            );
+            *shared = current;
+        }
+    }
+
+    /// 替换凭证（模拟刷新 Token 轮换 refreshToken）
+    #[cfg(test)]
+    pub(crate) fn set_credentials(
+        &mut self,
+        credentials: crate::kiro::model::credentials::KiroCredentials,
+    ) {
+        let tls = self.token_manager.tls_options().clone();
+        let config = self.token_manager.config().clone();
+        self.token_manager = TokenManager::new(config, credentials).with_tls_options(tls);
     }
 
     /// 构建请求头
-    fn build_headers(&self, token: &str) -> anyhow::Result<HeaderMap> {
-        let credentials = self.token_manager.credentials();
+    fn build_headers(&self, token: &str, host: &str) -> anyhow::Result<HeaderMap> {
         let config = self.token_manager.config();
 
-        let machine_id = machine_id::generate_from_credentials(credentials, config)
+        let machine_id = self
+            .machine_id()
             .ok_or_else(|| anyhow::anyhow!("无法生成 machine_id，请检查凭证配置"))?;
 
         let kiro_version = &config.kiro_version;
@@ -78,7 +206,7 @@ impl KiroProvider {
             reqwest::header::USER_AGENT,
             HeaderValue::from_str(&user_agent).unwrap(),
         );
-        headers.insert(HOST, HeaderValue::from_str(&self.base_domain()).unwrap());
+        headers.insert(HOST, HeaderValue::from_str(host).unwrap());
         headers.insert(
             "amz-sdk-invocation-id",
             HeaderValue::from_str(&Uuid::new_v4().to_string()).unwrap(),
@@ -98,64 +226,281 @@ impl KiroProvider {
 
     /// 发送非流式 API 请求
     ///
+    /// 主区域返回 5xx 且凭证配置了 `fallbackRegion` 时，会改用备用区域重试一次
+    ///
     /// # Arguments
     /// * `request_body` - JSON 格式的请求体字符串
+    /// * `timeout` - 本次请求的超时时间（可选），覆盖客户端默认的 12 分钟超时
+    /// * `deadline` - 整个请求的重试截止时间（可选，见 [`Self::retry_deadline`]）
     ///
     /// # Returns
     /// 返回原始的 HTTP Response，不做解析
-    pub async fn call_api(&mut self, request_body: &str) -> anyhow::Result<reqwest::Response> {
-        let token = self.token_manager.ensure_valid_token().await?;
-        let url = self.base_url();
-        let headers = self.build_headers(&token)?;
-
-        let response = self
-            .client
-            .post(&url)
-            .headers(headers)
-            .body(request_body.to_string())
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("API 请求失败: {} {}", status, body);
-        }
-
-        Ok(response)
+    pub async fn call_api(
+        &mut self,
+        request_body: &str,
+        timeout: Option<Duration>,
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<reqwest::Response> {
+        self.send_request(request_body, timeout, deadline, "API 请求失败").await
     }
 
     /// 发送流式 API 请求
     ///
+    /// 区域回退行为与 `call_api` 相同
+    ///
     /// # Arguments
     /// * `request_body` - JSON 格式的请求体字符串
+    /// * `timeout` - 本次请求的超时时间（可选，覆盖整个流的读取），覆盖客户端默认的 12 分钟超时
+    /// * `deadline` - 整个请求的重试截止时间（可选，见 [`Self::retry_deadline`]）
     ///
     /// # Returns
     /// 返回原始的 HTTP Response，调用方负责处理流式数据
     pub async fn call_api_stream(
         &mut self,
         request_body: &str,
+        timeout: Option<Duration>,
+        deadline: Option<Instant>,
     ) -> anyhow::Result<reqwest::Response> {
-        let token = self.token_manager.ensure_valid_token().await?;
-        let url = self.base_url();
-        let headers = self.build_headers(&token)?;
-
-        let response = self
-            .client
-            .post(&url)
-            .headers(headers)
-            .body(request_body.to_string())
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("流式 API 请求失败: {} {}", status, body);
+        self.send_request(request_body, timeout, deadline, "流式 API 请求失败").await
+    }
+
+    /// 按 `requestRetryDeadlineMs` 计算从现在开始的重试截止时间，未配置时返回 `None`
+    ///
+    /// 调用方应为每个客户端请求只计算一次，并在模型回退的各次调用间共用
+    pub fn retry_deadline(&self) -> Option<Instant> {
+        self.token_manager
+            .config()
+            .request_retry_deadline_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms))
+    }
+
+    /// 依次向候选区域发送请求
+    ///
+    /// `deadline` 约束 Token 刷新重试、主动等待、连接错误重试和区域回退
+    async fn send_request(
+        &mut self,
+        request_body: &str,
+        timeout: Option<Duration>,
+        deadline: Option<Instant>,
+        error_label: &str,
+    ) -> anyhow::Result<reqwest::Response> {
+        let token = self.token_manager.ensure_valid_token(deadline).await?;
+        self.sync_machine_id();
+        let regions = self.candidate_regions();
+
+        // 上游 429 比例过高时主动放缓，减轻上游压力（等待会超出重试预算时跳过）
+        let config = self.token_manager.config();
+        if let Some(threshold) = config.throttle_delay_threshold
+            && self.throttle_tracker.should_delay(threshold)
+        {
+            let delay = Duration::from_millis(config.throttle_delay_ms);
+            if deadline.is_some_and(|deadline| Instant::now() + delay > deadline) {
+                tracing::warn!("上游 429 比例超过 {}，但重试时间预算不足，跳过主动等待", threshold);
+            } else {
+                tracing::warn!("上游 429 比例超过 {}，发送前等待 {:?}", threshold, delay);
+                tokio::time::sleep(delay).await;
+            }
+        }
+
+        let retry_policy = NetworkRetryPolicy {
+            retries: config.network_retries,
+            base_delay: Duration::from_millis(config.network_retry_backoff_ms),
+            deadline,
+        };
+
+        send_with_region_fallback(&regions, error_label, deadline, |region| {
+            let request = self
+                .base_domain_for_region(region)
+                .and_then(|host| self.build_headers(&token, &host))
+                .map(|headers| {
+                    let request = self
+                        .client
+                        .post(self.base_url_for_region(region))
+                        .headers(headers)
+                        .body(request_body.to_string());
+                    match timeout {
+                        Some(timeout) => request.timeout(timeout),
+                        None => request,
+                    }
+                });
+            let throttle_tracker = self.throttle_tracker.clone();
+            async move {
+                let response = send_with_network_retry(request?, retry_policy).await?;
+                throttle_tracker
+                    .record(response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS);
+                Ok(response)
+            }
+        })
+        .await
+    }
+}
+
+/// 连接错误的重试策略
+#[derive(Debug, Clone, Copy)]
+struct NetworkRetryPolicy {
+    /// 最大重试次数（不含首次尝试）
+    retries: u32,
+    /// 首次重试前的等待时间，之后每次翻倍
+    base_delay: Duration,
+    /// 整个请求的重试截止时间（可选），等待后会超过该时间时不再重试
+    deadline: Option<Instant>,
+}
+
+/// 发送请求，遇到连接错误时重试
+///
+/// 连接被重置、DNS 失败等网络错误与凭证无关，短暂等待后重试即可；
+/// 超时不重试（上游可能仍在处理），直接返回并在错误信息中注明
+///
+/// # Arguments
+/// * `request` - 待发送的请求（请求体需可克隆，否则只发送一次）
+/// * `policy` - 重试次数、退避时间与截止时间
+async fn send_with_network_retry(
+    request: reqwest::RequestBuilder,
+    policy: NetworkRetryPolicy,
+) -> anyhow::Result<reqwest::Response> {
+    let NetworkRetryPolicy {
+        retries,
+        base_delay,
+        deadline,
+    } = policy;
+    let mut attempt = 0;
+    loop {
+        let retryable = if attempt < retries {
+            request.try_clone()
+        } else {
+            None
+        };
+        let Some(current) = retryable else {
+            // 最后一次尝试
+            return request
+                .send()
+                .await
+                .map_err(|e| describe_send_error(e, attempt));
+        };
+
+        match current.send().await {
+            Ok(response) => return Ok(response),
+            Err(e) if is_connection_error(&e) => {
+                let delay = base_delay.saturating_mul(2u32.saturating_pow(attempt));
+                if deadline.is_some_and(|deadline| Instant::now() + delay > deadline) {
+                    tracing::warn!("上游连接失败（{}），重试时间预算已用尽，不再重试", e);
+                    return Err(describe_send_error(e, attempt));
+                }
+                attempt += 1;
+                tracing::warn!(
+                    "上游连接失败（{}），{:?} 后进行第 {}/{} 次重试",
+                    e,
+                    delay,
+                    attempt,
+                    retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(describe_send_error(e, attempt)),
+        }
+    }
+}
+
+/// 是否为可重试的连接错误（连接被拒绝、DNS 解析失败等），超时不算
+///
+/// 只重试建立连接阶段的失败：请求发出后连接才断开时上游可能已经开始生成，
+/// 重放 `generateAssistantResponse` 会导致重复生成和重复计费
+fn is_connection_error(error: &reqwest::Error) -> bool {
+    !error.is_timeout() && error.is_connect()
+}
+
+/// 为发送错误附加说明，区分超时与连接错误（保留原始错误，便于调用方判断类型）
+fn describe_send_error(error: reqwest::Error, retried: u32) -> anyhow::Error {
+    let message = if error.is_timeout() {
+        format!("上游请求超时: {}", error)
+    } else if is_connection_error(&error) {
+        format!("上游连接失败（已重试 {} 次）: {}", retried, error)
+    } else {
+        return error.into();
+    };
+    anyhow::Error::new(error).context(message)
+}
+
+/// 默认的区域 API 域名
+fn default_domain_for_region(region: &str) -> String {
+    format!("q.{}.amazonaws.com", region)
+}
+
+/// 上游返回非成功状态码的错误
+///
+/// 保留状态码，便于调用方区分错误类型（如模型被拒绝时切换备选模型）
+#[derive(Debug)]
+pub struct UpstreamStatusError {
+    pub label: String,
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+impl std::fmt::Display for UpstreamStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} {}", self.label, self.status, self.body)
+    }
+}
+
+impl std::error::Error for UpstreamStatusError {}
+
+/// 按顺序向各区域发送请求，直到成功
+///
+/// 只有 5xx（上游区域故障）才会切换到下一个区域，4xx 等错误直接返回；
+/// 已超过重试截止时间时也不再切换
+async fn send_with_region_fallback<F, Fut>(
+    regions: &[String],
+    error_label: &str,
+    deadline: Option<Instant>,
+    mut send: F,
+) -> anyhow::Result<reqwest::Response>
+where
+    F: FnMut(&str) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<reqwest::Response>>,
+{
+    for (i, region) in regions.iter().enumerate() {
+        let response = send(region).await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        if let Some(next) = regions.get(i + 1)
+            && status.is_server_error()
+        {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                tracing::warn!(
+                    "区域 {} 返回 {}，重试时间预算已用尽，不再切换到备用区域 {}",
+                    region,
+                    status,
+                    next
+                );
+                return Err(UpstreamStatusError {
+                    label: error_label.to_string(),
+                    status,
+                    body,
+                }
+                .into());
+            }
+            tracing::warn!(
+                "区域 {} 返回 {}，切换到备用区域 {} 重试",
+                region,
+                status,
+                next
+            );
+            continue;
         }
 
-        Ok(response)
+        return Err(UpstreamStatusError {
+            label: error_label.to_string(),
+            status,
+            body,
+        }
+        .into());
     }
+
+    anyhow::bail!("{}: 没有可用的区域", error_label)
 }
 
 #[cfg(test)]
@@ -169,7 +514,7 @@ mod tests {
         let config = Config::default();
         let credentials = KiroCredentials::default();
         let tm = TokenManager::new(config, credentials);
-        let provider = KiroProvider::new(tm);
+        let provider = KiroProvider::new(tm).unwrap();
         assert!(provider.base_url().contains("amazonaws.com"));
         assert!(provider.base_url().contains("generateAssistantResponse"));
     }
@@ -180,8 +525,47 @@ mod tests {
         config.region = "us-east-1".to_string();
         let credentials = KiroCredentials::default();
         let tm = TokenManager::new(config, credentials);
-        let provider = KiroProvider::new(tm);
-        assert_eq!(provider.base_domain(), "q.us-east-1.amazonaws.com");
+        let provider = KiroProvider::new(tm).unwrap();
+        assert_eq!(
+            provider.base_domain_for_region("us-east-1").unwrap(),
+            "q.us-east-1.amazonaws.com"
+        );
+        // 第二次读取命中缓存
+        assert_eq!(
+            provider.base_domain_for_region("us-east-1").unwrap(),
+            "q.us-east-1.amazonaws.com"
+        );
+        assert_eq!(provider.domain_cache.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_base_domain_rejects_invalid_region() {
+        let tm = TokenManager::new(Config::default(), KiroCredentials::default());
+        let provider = KiroProvider::new(tm).unwrap();
+
+        for region in ["", "us-east", "US-EAST-1", "us_east_1", "evil.com/us-east-1"] {
+            let err = provider.base_domain_for_region(region).unwrap_err();
+            assert!(err.to_string().contains("无效的区域"), "{}", region);
+        }
+        assert!(provider.domain_cache.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_region_fails_fast() {
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let config = Config {
+            region: "not a region".to_string(),
+            ..Default::default()
+        };
+        let mut provider = KiroProvider::new(TokenManager::new(config, credentials)).unwrap();
+
+        let err = provider.call_api("{}", None, None).await.unwrap_err();
+        assert!(err.to_string().contains("无效的区域"));
     }
 
     #[test]
@@ -195,8 +579,8 @@ mod tests {
         credentials.refresh_token = Some("a".repeat(150));
 
         let tm = TokenManager::new(config, credentials);
-        let provider = KiroProvider::new(tm);
-        let headers = provider.build_headers("test_token").unwrap();
+        let provider = KiroProvider::new(tm).unwrap();
+        let headers = provider.build_headers("test_token", &provider.base_domain_for_region("us-east-1").unwrap()).unwrap();
 
         assert_eq!(headers.get(CONTENT_TYPE).unwrap(), "application/json");
         assert_eq!(
@@ -212,4 +596,313 @@ mod tests {
             .starts_with("Bearer "));
         assert_eq!(headers.get(CONNECTION).unwrap(), "close");
     }
+
+    #[test]
+    fn test_provider_uses_credential_proxy() {
+        let credentials = KiroCredentials {
+            proxy_url: Some("http://127.0.0.1:18081".to_string()),
+            ..Default::default()
+        };
+        let tm = TokenManager::new(Config::default(), credentials);
+        let provider = KiroProvider::new(tm).unwrap();
+        assert!(format!("{:?}", provider.client).contains("127.0.0.1:18081"));
+    }
+
+    #[test]
+    fn test_provider_rejects_invalid_proxy() {
+        let credentials = KiroCredentials {
+            proxy_url: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        let tm = TokenManager::new(Config::default(), credentials);
+        assert!(KiroProvider::new(tm).is_err());
+    }
+
+    fn mock_response(status: u16) -> reqwest::Response {
+        reqwest::Response::from(
+            http::Response::builder()
+                .status(status)
+                .body(String::new())
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_candidate_regions_with_fallback() {
+        let credentials = KiroCredentials {
+            fallback_region: Some("us-west-2".to_string()),
+            ..Default::default()
+        };
+        let tm = TokenManager::new(Config::default(), credentials);
+        let provider = KiroProvider::new(tm).unwrap();
+        assert_eq!(provider.candidate_regions(), vec!["us-east-1", "us-west-2"]);
+        assert_eq!(
+            provider.base_url_for_region("us-west-2"),
+            "https://q.us-west-2.amazonaws.com/generateAssistantResponse"
+        );
+    }
+
+    #[test]
+    fn test_candidate_regions_ignores_same_fallback() {
+        let credentials = KiroCredentials {
+            fallback_region: Some("us-east-1".to_string()),
+            ..Default::default()
+        };
+        let tm = TokenManager::new(Config::default(), credentials);
+        let provider = KiroProvider::new(tm).unwrap();
+        assert_eq!(provider.candidate_regions(), vec!["us-east-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_primary_5xx_retries_fallback_region() {
+        let regions = vec!["us-east-1".to_string(), "us-west-2".to_string()];
+        let mut attempted = Vec::new();
+
+        let response = send_with_region_fallback(&regions, "API 请求失败", None, |region| {
+            attempted.push(region.to_string());
+            let status = if region == "us-east-1" { 503 } else { 200 };
+            async move { Ok(mock_response(status)) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(attempted, vec!["us-east-1", "us-west-2"]);
+    }
+
+    #[tokio::test]
+    async fn test_client_error_does_not_fall_back() {
+        let regions = vec!["us-east-1".to_string(), "us-west-2".to_string()];
+        let mut attempted = Vec::new();
+
+        let result = send_with_region_fallback(&regions, "API 请求失败", None, |region| {
+            attempted.push(region.to_string());
+            async move { Ok(mock_response(403)) }
+        })
+        .await;
+
+        assert!(result.unwrap_err().to_string().contains("403"));
+        assert_eq!(attempted, vec!["us-east-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_region_fallback_stops_at_deadline() {
+        let regions = vec!["us-east-1".to_string(), "us-west-2".to_string()];
+        let mut attempted = Vec::new();
+
+        // 主区域返回 5xx 时预算已用尽，不再尝试备用区域
+        let deadline = Some(Instant::now());
+        let result = send_with_region_fallback(&regions, "API 请求失败", deadline, |region| {
+            attempted.push(region.to_string());
+            async move { Ok(mock_response(503)) }
+        })
+        .await;
+
+        assert!(result.unwrap_err().to_string().contains("503"));
+        assert_eq!(attempted, vec!["us-east-1"]);
+    }
+
+    #[test]
+    fn test_upstream_base_override() {
+        let config = Config {
+            upstream_base_override: Some(
+                "http://127.0.0.1:9000/{region}/generateAssistantResponse".to_string(),
+            ),
+            ..Default::default()
+        };
+        let tm = TokenManager::new(config, KiroCredentials::default());
+        let provider = KiroProvider::new(tm).unwrap();
+
+        assert_eq!(
+            provider.base_url(),
+            "http://127.0.0.1:9000/us-east-1/generateAssistantResponse"
+        );
+        assert_eq!(provider.base_domain_for_region("us-east-1").unwrap(), "127.0.0.1:9000");
+    }
+
+    #[tokio::test]
+    async fn test_call_api_uses_upstream_override() {
+        use axum::{Router, http::HeaderMap, routing::post};
+
+        let app = Router::new().route(
+            "/us-east-1/generateAssistantResponse",
+            post(|headers: HeaderMap| async move {
+                headers
+                    .get(HOST)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string()
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let config = Config {
+            upstream_base_override: Some(format!(
+                "http://{}/{{region}}/generateAssistantResponse",
+                addr
+            )),
+            ..Default::default()
+        };
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let mut provider = KiroProvider::new(TokenManager::new(config, credentials)).unwrap();
+
+        let response = provider.call_api("{}", None, None).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), addr.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout_override() {
+        use axum::{Router, routing::post};
+
+        let app = Router::new().route(
+            "/generateAssistantResponse",
+            post(|| async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                "done"
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let config = Config {
+            upstream_base_override: Some(format!("http://{}/generateAssistantResponse", addr)),
+            ..Default::default()
+        };
+        let credentials = KiroCredentials {
+            access_token: Some("token".to_string()),
+            refresh_token: Some("a".repeat(150)),
+            expires_at: Some((chrono::Utc::now() + chrono::Duration::hours(1)).to_rfc3339()),
+            ..Default::default()
+        };
+        let mut provider = KiroProvider::new(TokenManager::new(config, credentials)).unwrap();
+
+        // 较短的单次超时让慢上游超时
+        let err = provider
+            .call_api("{}", Some(Duration::from_millis(100)), None)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<reqwest::Error>().unwrap().is_timeout());
+
+        // 默认超时下同一请求正常完成
+        let response = provider.call_api("{}", None, None).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "done");
+    }
+
+    /// 启动一个先断开前 `drops` 个连接、之后正常响应的上游
+    async fn spawn_flaky_upstream(drops: usize) -> String {
+        use axum::{Router, routing::post};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for _ in 0..drops {
+                let (stream, _) = listener.accept().await.unwrap();
+                drop(stream);
+            }
+            let app = Router::new().route("/generateAssistantResponse", post(|| async { "ok" }));
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/generateAssistantResponse", addr)
+    }
+
+    /// 返回一个当前无人监听的端口地址，`start_after` 不为空时在该时间后开始正常响应
+    async fn spawn_late_upstream(start_after: Option<Duration>) -> String {
+        use axum::{Router, routing::post};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        if let Some(delay) = start_after {
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+                let app =
+                    Router::new().route("/generateAssistantResponse", post(|| async { "ok" }));
+                axum::serve(listener, app).await.unwrap();
+            });
+        }
+        format!("http://{}/generateAssistantResponse", addr)
+    }
+
+    fn retry_policy(retries: u32) -> NetworkRetryPolicy {
+        NetworkRetryPolicy {
+            retries,
+            base_delay: Duration::ZERO,
+            deadline: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_network_retry_after_connect_error() {
+        let client = reqwest::Client::new();
+
+        // 首次连接被拒绝，上游启动后重试成功
+        let url = spawn_late_upstream(Some(Duration::from_millis(50))).await;
+        let policy = NetworkRetryPolicy {
+            retries: 1,
+            base_delay: Duration::from_millis(300),
+            deadline: None,
+        };
+        let response = send_with_network_retry(client.post(&url).body("{}"), policy)
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        // 重试次数用尽后失败，错误信息注明为连接错误
+        let url = spawn_late_upstream(None).await;
+        let err = send_with_network_retry(client.post(&url).body("{}"), retry_policy(1))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("上游连接失败（已重试 1 次）"));
+        assert!(err.downcast_ref::<reqwest::Error>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_after_request_sent() {
+        // 关闭连接复用，确保重试时会建立新连接
+        let client = reqwest::Client::builder()
+            .pool_max_idle_per_host(0)
+            .build()
+            .unwrap();
+
+        // 连接建立后被断开：请求可能已被上游处理，不重试（否则第二次会成功）
+        let url = spawn_flaky_upstream(1).await;
+        let err = send_with_network_retry(client.post(&url).body("{}"), retry_policy(1))
+            .await
+            .unwrap_err();
+        assert!(!err.to_string().contains("已重试"));
+        assert!(!err.downcast_ref::<reqwest::Error>().unwrap().is_connect());
+    }
+
+    #[tokio::test]
+    async fn test_network_retry_stops_at_deadline() {
+        let client = reqwest::Client::new();
+        let url = spawn_late_upstream(None).await;
+
+        // 退避 20ms、40ms、80ms...，100ms 的预算只够重试两次
+        let started = Instant::now();
+        let policy = NetworkRetryPolicy {
+            retries: 10,
+            base_delay: Duration::from_millis(20),
+            deadline: Some(started + Duration::from_millis(100)),
+        };
+        let err = send_with_network_retry(client.post(&url).body("{}"), policy)
+            .await
+            .unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert!(err.to_string().contains("上游连接失败（已重试 2 次）"));
+    }
 }
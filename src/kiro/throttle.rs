@@ -0,0 +1,151 @@
+//! 上游限流统计
+//!
+//! 在滚动时间窗口内统计上游响应中 429 所占的比例，用于观察上游限流情况，
+//! 并在比例过高时由 Provider 在发送请求前主动等待一小段时间，减轻对上游的压力。
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// 窗口内保留的最大样本数，防止极端流量下无限增长
+const MAX_SAMPLES: usize = 10_000;
+
+/// 窗口内样本数少于该值时不触发主动延迟，避免少量请求造成误判
+const MIN_SAMPLES_FOR_DELAY: usize = 10;
+
+/// 限流统计结果
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThrottleStats {
+    /// 统计窗口（秒）
+    pub window_secs: u64,
+    /// 窗口内的上游响应数
+    pub total: usize,
+    /// 窗口内的 429 响应数
+    pub throttled: usize,
+    /// 429 比例（0.0 ~ 1.0）
+    pub rate: f64,
+}
+
+/// 上游限流统计器
+pub struct ThrottleTracker {
+    window: Duration,
+    /// (响应时间, 是否为 429)
+    samples: Mutex<VecDeque<(Instant, bool)>>,
+}
+
+impl ThrottleTracker {
+    /// 创建统计器
+    ///
+    /// # Arguments
+    /// * `window` - 滚动统计窗口
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// 记录一次上游响应
+    pub fn record(&self, throttled: bool) {
+        self.record_at(Instant::now(), throttled);
+    }
+
+    fn record_at(&self, now: Instant, throttled: bool) {
+        let mut samples = self.samples.lock().unwrap();
+        self.prune(&mut samples, now);
+        if samples.len() >= MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back((now, throttled));
+    }
+
+    /// 获取当前窗口的统计结果
+    pub fn stats(&self) -> ThrottleStats {
+        self.stats_at(Instant::now())
+    }
+
+    fn stats_at(&self, now: Instant) -> ThrottleStats {
+        let mut samples = self.samples.lock().unwrap();
+        self.prune(&mut samples, now);
+
+        let total = samples.len();
+        let throttled = samples.iter().filter(|(_, throttled)| *throttled).count();
+        ThrottleStats {
+            window_secs: self.window.as_secs(),
+            total,
+            throttled,
+            rate: if total == 0 {
+                0.0
+            } else {
+                throttled as f64 / total as f64
+            },
+        }
+    }
+
+    /// 429 比例超过阈值时返回是否应在发送前主动等待
+    pub fn should_delay(&self, threshold: f64) -> bool {
+        let stats = self.stats();
+        stats.total >= MIN_SAMPLES_FOR_DELAY && stats.rate > threshold
+    }
+
+    fn prune(&self, samples: &mut VecDeque<(Instant, bool)>, now: Instant) {
+        while let Some((at, _)) = samples.front() {
+            if now.saturating_duration_since(*at) > self.window {
+                samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_over_mixed_responses() {
+        let tracker = ThrottleTracker::new(Duration::from_secs(60));
+        let now = Instant::now();
+        for i in 0..20 {
+            tracker.record_at(now, i % 4 == 0);
+        }
+
+        let stats = tracker.stats_at(now);
+        assert_eq!(stats.total, 20);
+        assert_eq!(stats.throttled, 5);
+        assert!((stats.rate - 0.25).abs() < f64::EPSILON);
+        assert_eq!(stats.window_secs, 60);
+    }
+
+    #[test]
+    fn test_old_samples_leave_window() {
+        let tracker = ThrottleTracker::new(Duration::from_secs(60));
+        let start = Instant::now();
+        for _ in 0..10 {
+            tracker.record_at(start, true);
+        }
+        let later = start + Duration::from_secs(61);
+        tracker.record_at(later, false);
+
+        let stats = tracker.stats_at(later);
+        assert_eq!((stats.total, stats.throttled), (1, 0));
+        assert_eq!(stats.rate, 0.0);
+    }
+
+    #[test]
+    fn test_should_delay_requires_enough_samples() {
+        let tracker = ThrottleTracker::new(Duration::from_secs(60));
+        for _ in 0..MIN_SAMPLES_FOR_DELAY - 1 {
+            tracker.record(true);
+        }
+        assert!(!tracker.should_delay(0.5));
+
+        tracker.record(true);
+        assert!(tracker.should_delay(0.5));
+        assert!(!tracker.should_delay(1.0));
+    }
+}
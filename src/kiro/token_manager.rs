@@ -2,9 +2,12 @@
 //!
 //! 负责 Token 过期检测和刷新，支持 Social 和 IdC 认证方式
 
+use std::time::{Duration as StdDuration, Instant};
+
 use anyhow::bail;
 use chrono::{DateTime, Duration, Utc};
 
+use crate::kiro::http_client::{build_client, TlsOptions};
 use crate::kiro::machine_id;
 use crate::kiro::model::credentials::KiroCredentials;
 use crate::kiro::model::token_refresh::{
@@ -18,6 +21,8 @@ use crate::model::config::Config;
 pub struct TokenManager {
     config: Config,
     credentials: KiroCredentials,
+    /// 刷新请求使用的 TLS 加固选项（启动时构建一次，刷新时复用）
+    tls: TlsOptions,
 }
 
 impl TokenManager {
@@ -26,9 +31,19 @@ impl TokenManager {
         Self {
             config,
             credentials,
+            tls: TlsOptions::default(),
         }
     }
 
+    /// 设置刷新请求使用的 TLS 加固选项
+    ///
+    /// 由 [`KiroProvider::new`](crate::kiro::provider::KiroProvider::new) 传入启动时构建的选项，
+    /// 避免每次刷新都重新读取 CA 文件
+    pub fn with_tls_options(mut self, tls: TlsOptions) -> Self {
+        self.tls = tls;
+        self
+    }
+
     /// 获取凭据的引用
     pub fn credentials(&self) -> &KiroCredentials {
         &self.credentials
@@ -39,16 +54,31 @@ impl TokenManager {
         &self.config
     }
 
+    /// 获取 TLS 加固选项的引用
+    pub fn tls_options(&self) -> &TlsOptions {
+        &self.tls
+    }
+
     /// 确保获取有效的访问 Token
     ///
     /// 如果 Token 过期或即将过期，会自动刷新
-    pub async fn ensure_valid_token(&mut self) -> anyhow::Result<String> {
+    ///
+    /// # Arguments
+    /// * `deadline` - 请求的重试截止时间（可选），刷新重试的等待会超过该时间时不再重试
+    pub async fn ensure_valid_token(&mut self, deadline: Option<Instant>) -> anyhow::Result<String> {
         if is_token_expired(&self.credentials) || is_token_expiring_soon(&self.credentials) {
-            self.credentials = refresh_token(&self.credentials, &self.config).await?;
+            let refreshed = refresh_with_retry(
+                self.config.token_refresh_retries,
+                StdDuration::from_millis(self.config.token_refresh_backoff_ms),
+                deadline,
+                || refresh_token(&self.credentials, &self.config, &self.tls),
+            )
+            .await?;
+            self.credentials = refreshed;
 
             // 刷新后再次检查 token 时间有效性
             if is_token_expired(&self.credentials) {
-                anyhow::bail!("刷新后的 Token 仍然无效或已过期");
+                anyhow::bail!("刷新后的 Token 仍然无效或已过期，请检查本机时钟是否准确");
             }
         }
 
@@ -59,6 +89,76 @@ impl TokenManager {
     }
 }
 
+/// Token 刷新接口返回非成功状态码的错误
+#[derive(Debug)]
+struct RefreshStatusError {
+    message: &'static str,
+    status: reqwest::StatusCode,
+    body: String,
+}
+
+impl std::fmt::Display for RefreshStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} {}", self.message, self.status, self.body)
+    }
+}
+
+impl std::error::Error for RefreshStatusError {}
+
+/// 判断刷新失败是否为暂时性错误（网络异常、限流、服务端错误），值得重试
+///
+/// `invalid_grant` 等 4xx 错误以及凭证校验失败属于永久错误，重试无意义
+fn is_transient_refresh_error(error: &anyhow::Error) -> bool {
+    if let Some(e) = error.downcast_ref::<RefreshStatusError>() {
+        return e.status == reqwest::StatusCode::TOO_MANY_REQUESTS || e.status.is_server_error();
+    }
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_timeout() || e.is_connect() || e.is_request())
+}
+
+/// 刷新 Token，暂时性错误时按指数退避重试
+///
+/// # Arguments
+/// * `retries` - 最大重试次数（不含首次尝试）
+/// * `base_delay` - 首次重试前的等待时间，之后每次翻倍
+/// * `deadline` - 截止时间（可选），等待后会超过该时间时不再重试
+/// * `refresh` - 执行一次刷新
+async fn refresh_with_retry<F, Fut>(
+    retries: u32,
+    base_delay: StdDuration,
+    deadline: Option<Instant>,
+    mut refresh: F,
+) -> anyhow::Result<KiroCredentials>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<KiroCredentials>>,
+{
+    let mut attempt = 0;
+    loop {
+        match refresh().await {
+            Ok(credentials) => return Ok(credentials),
+            Err(e) if attempt < retries && is_transient_refresh_error(&e) => {
+                let delay = base_delay.saturating_mul(2u32.saturating_pow(attempt));
+                if deadline.is_some_and(|deadline| Instant::now() + delay > deadline) {
+                    tracing::warn!("Token 刷新失败（{}），重试时间预算已用尽，不再重试", e);
+                    return Err(e);
+                }
+                attempt += 1;
+                tracing::warn!(
+                    "Token 刷新失败（{}），{:?} 后进行第 {}/{} 次重试",
+                    e,
+                    delay,
+                    attempt,
+                    retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 /// 检查 Token 是否在指定时间内过期
 fn is_token_expiring_within(credentials: &KiroCredentials, minutes: i64) -> Option<bool> {
     credentials
@@ -107,6 +207,7 @@ fn validate_refresh_token(credentials: &KiroCredentials) -> anyhow::Result<()> {
 async fn refresh_token(
     credentials: &KiroCredentials,
     config: &Config,
+    tls: &TlsOptions,
 ) -> anyhow::Result<KiroCredentials> {
     validate_refresh_token(credentials)?;
 
@@ -114,8 +215,8 @@ async fn refresh_token(
     let auth_method = credentials.auth_method.as_deref().unwrap_or("social");
 
     match auth_method.to_lowercase().as_str() {
-        "idc" | "builder-id" => refresh_idc_token(credentials, config).await,
-        _ => refresh_social_token(credentials, config).await,
+        "idc" | "builder-id" => refresh_idc_token(credentials, config, tls).await,
+        _ => refresh_social_token(credentials, config, tls).await,
     }
 }
 
@@ -123,6 +224,7 @@ async fn refresh_token(
 async fn refresh_social_token(
     credentials: &KiroCredentials,
     config: &Config,
+    tls: &TlsOptions,
 ) -> anyhow::Result<KiroCredentials> {
     tracing::info!("正在刷新 Social Token...");
 
@@ -135,7 +237,7 @@ async fn refresh_social_token(
         .ok_or_else(|| anyhow::anyhow!("无法生成 machineId"))?;
     let kiro_version = &config.kiro_version;
 
-    let client = reqwest::Client::new();
+    let client = build_client(credentials.proxy_url.as_deref(), None, tls)?;
     let body = RefreshRequest {
         refresh_token: refresh_token.to_string(),
     };
@@ -165,7 +267,12 @@ async fn refresh_social_token(
             500..=599 => "服务器错误，AWS OAuth 服务暂时不可用",
             _ => "Token 刷新失败",
         };
-        bail!("{}: {} {}", error_msg, status, body_text);
+        return Err(RefreshStatusError {
+            message: error_msg,
+            status,
+            body: body_text,
+        }
+        .into());
     }
 
     let data: RefreshResponse = response.json().await?;
@@ -197,6 +304,7 @@ const IDC_AMZ_USER_AGENT: &str =
 async fn refresh_idc_token(
     credentials: &KiroCredentials,
     config: &Config,
+    tls: &TlsOptions,
 ) -> anyhow::Result<KiroCredentials> {
     tracing::info!("正在刷新 IdC Token...");
 
@@ -213,7 +321,7 @@ async fn refresh_idc_token(
     let region = &config.region;
     let refresh_url = format!("https://oidc.{}.amazonaws.com/token", region);
 
-    let client = reqwest::Client::new();
+    let client = build_client(credentials.proxy_url.as_deref(), None, tls)?;
     let body = IdcRefreshRequest {
         client_id: client_id.to_string(),
         client_secret: client_secret.to_string(),
@@ -246,7 +354,12 @@ async fn refresh_idc_token(
             500..=599 => "服务器错误，AWS OIDC 服务暂时不可用",
             _ => "IdC Token 刷新失败",
         };
-        bail!("{}: {} {}", error_msg, status, body_text);
+        return Err(RefreshStatusError {
+            message: error_msg,
+            status,
+            body: body_text,
+        }
+        .into());
     }
 
     let data: IdcRefreshResponse = response.json().await?;
@@ -337,4 +450,75 @@ mod tests {
         let result = validate_refresh_token(&credentials);
         assert!(result.is_ok());
     }
+
+    fn status_error(status: u16, body: &str) -> anyhow::Error {
+        RefreshStatusError {
+            message: "Token 刷新失败",
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            body: body.to_string(),
+        }
+        .into()
+    }
+
+    #[tokio::test]
+    async fn test_refresh_retries_transient_failure() {
+        let mut attempts = 0;
+        let result = refresh_with_retry(2, StdDuration::from_millis(1), None, || {
+            attempts += 1;
+            let outcome = if attempts == 1 {
+                Err(status_error(503, "Service Unavailable"))
+            } else {
+                Ok(KiroCredentials {
+                    access_token: Some("new-token".to_string()),
+                    ..Default::default()
+                })
+            };
+            async move { outcome }
+        })
+        .await;
+
+        assert_eq!(result.unwrap().access_token.as_deref(), Some("new-token"));
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_invalid_grant_fails_fast() {
+        let mut attempts = 0;
+        let result = refresh_with_retry(3, StdDuration::from_millis(1), None, || {
+            attempts += 1;
+            async { Err(status_error(400, r#"{"error":"invalid_grant"}"#)) }
+        })
+        .await;
+
+        assert!(result.unwrap_err().to_string().contains("invalid_grant"));
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_gives_up_after_retries() {
+        let mut attempts = 0;
+        let result = refresh_with_retry(2, StdDuration::from_millis(1), None, || {
+            attempts += 1;
+            async { Err(status_error(500, "")) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_retry_stops_at_deadline() {
+        let mut attempts = 0;
+        let deadline = Instant::now() + StdDuration::from_millis(50);
+        let result = refresh_with_retry(5, StdDuration::from_millis(40), Some(deadline), || {
+            attempts += 1;
+            async { Err(status_error(503, "")) }
+        })
+        .await;
+
+        // 第一次重试等待 40ms 仍在预算内，第二次需等待 80ms，超出预算
+        assert!(result.is_err());
+        assert_eq!(attempts, 2);
+    }
 }
@@ -4,7 +4,8 @@ mod model;
 pub mod token;
 
 use clap::Parser;
-use kiro::model::credentials::KiroCredentials;
+use kiro::clock_skew::ClockSkewMonitor;
+use kiro::model::credentials::{CredentialsFormat, KiroCredentials};
 use kiro::provider::KiroProvider;
 use kiro::token_manager::TokenManager;
 use model::config::Config;
@@ -23,15 +24,51 @@ async fn main() {
         )
         .init();
 
-    // 加载配置
     let config_path = args.config.unwrap_or_else(|| Config::default_config_path().to_string());
+    let credentials_path = args.credentials.unwrap_or_else(|| KiroCredentials::default_credentials_path().to_string());
+
+    // 从 Kiro IDE 导入凭证（不依赖配置文件，首次安装时也可使用）
+    if let Some(source) = args.import_from_kiro {
+        if let Err(e) = import_from_kiro(source, &credentials_path) {
+            tracing::error!("导入 Kiro IDE 凭证失败: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // 仅自检模式：检查完成后直接退出，不绑定端口
+    if args.check {
+        match self_check(&config_path, &credentials_path) {
+            Ok(()) => {
+                tracing::info!("自检通过（配置: {}，凭证: {}）", config_path, credentials_path);
+                return;
+            }
+            Err(errors) => {
+                tracing::error!("自检失败（{} 个问题）:", errors.len());
+                for error in &errors {
+                    tracing::error!("  - {}", error);
+                }
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // 加载配置
     let config = Config::load(&config_path).unwrap_or_else(|e| {
         tracing::error!("加载配置失败: {}", e);
         std::process::exit(1);
     });
 
+    // 校验配置
+    if let Err(errors) = config.validate() {
+        tracing::error!("配置校验失败（{}）:", config_path);
+        for error in &errors {
+            tracing::error!("  - {}", error);
+        }
+        std::process::exit(1);
+    }
+
     // 加载凭证
-    let credentials_path = args.credentials.unwrap_or_else(|| KiroCredentials::default_credentials_path().to_string());
     let credentials = KiroCredentials::load(&credentials_path).unwrap_or_else(|e| {
         tracing::error!("加载凭证失败: {}", e);
         std::process::exit(1);
@@ -47,17 +84,32 @@ async fn main() {
 
     // 创建 KiroProvider
     let token_manager = TokenManager::new(config.clone(), credentials.clone());
-    let kiro_provider = KiroProvider::new(token_manager);
+    let kiro_provider = KiroProvider::new(token_manager).unwrap_or_else(|e| {
+        tracing::error!("创建 KiroProvider 失败: {}", e);
+        std::process::exit(1);
+    });
+
+    // 启动时钟偏差检测（启动时一次，之后周期检测）
+    let clock_skew_monitor = ClockSkewMonitor::new(config.clock_skew_threshold_secs);
+    if let Err(e) = clock_skew_monitor.clone().spawn(
+        kiro_provider.base_url(),
+        credentials.proxy_url.as_deref(),
+        kiro_provider.tls_options(),
+    ) {
+        tracing::error!("启动时钟偏差检测失败: {}", e);
+        std::process::exit(1);
+    }
 
     // 初始化 count_tokens 配置
     token::init_config(token::CountTokensConfig {
         api_url: config.count_tokens_api_url.clone(),
         api_key: config.count_tokens_api_key.clone(),
         auth_type: config.count_tokens_auth_type.clone(),
+        protocol: config.count_tokens_protocol.clone(),
     });
 
     // 构建路由（从凭据获取 profile_arn）
-    let app = anthropic::create_router_with_provider(&api_key, Some(kiro_provider), credentials.profile_arn.clone());
+    let app = anthropic::create_router_with_provider(&config, &api_key, Some(kiro_provider), credentials.profile_arn.clone(), Some(clock_skew_monitor));
 
     // 启动服务器
     let addr = format!("{}:{}", config.host, config.port);
@@ -67,7 +119,178 @@ async fn main() {
     tracing::info!("  GET  /v1/models");
     tracing::info!("  POST /v1/messages");
     tracing::info!("  POST /v1/messages/count_tokens");
+    tracing::info!("  GET  /stats/latency");
+    tracing::info!("  GET  /stats/cache");
+    tracing::info!("  GET  /stats/throttling");
+    tracing::info!("  GET  /healthz");
+    tracing::info!("  GET  /version");
+    if anthropic::admin_api_enabled(&config) {
+        tracing::info!("  POST /admin/maintenance");
+        tracing::info!("  GET  /admin/machine-id");
+        tracing::info!("  POST /debug/decode");
+    } else {
+        tracing::info!("管理 API 未挂载（enableAdminApi = false 或未配置 adminApiKey）");
+    }
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
+
+/// 启动自检：依次检查配置、凭证与 Provider 初始化，收集所有问题后一并返回
+///
+/// 与正常启动使用相同的加载逻辑，但不绑定端口、不发起网络请求
+fn self_check(config_path: &str, credentials_path: &str) -> Result<(), Vec<String>> {
+    let mut errors = Vec::new();
+
+    let config = match Config::load(config_path) {
+        Ok(config) => {
+            // validate() 已包含 apiKey 未设置的检查
+            if let Err(validation_errors) = config.validate() {
+                errors.extend(validation_errors);
+            }
+            Some(config)
+        }
+        Err(e) => {
+            errors.push(format!("加载配置失败 {}: {}", config_path, e));
+            None
+        }
+    };
+
+    let credentials = match KiroCredentials::load(credentials_path) {
+        Ok(credentials) => {
+            if credentials.refresh_token.is_none() {
+                errors.push("凭证缺少 refreshToken".to_string());
+            }
+            Some(credentials)
+        }
+        Err(e) => {
+            errors.push(format!("加载凭证失败 {}: {}", credentials_path, e));
+            None
+        }
+    };
+
+    // 配置与凭证均无问题时，再验证 Provider 能否初始化（区域、代理、TLS 等）
+    if errors.is_empty()
+        && let (Some(config), Some(credentials)) = (config, credentials)
+        && let Err(e) = KiroProvider::new(TokenManager::new(config, credentials))
+    {
+        errors.push(format!("创建 KiroProvider 失败: {}", e));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// 从 Kiro IDE 导入凭证并写入凭证文件
+///
+/// 为避免覆盖已有凭证，目标文件已存在时报错
+fn import_from_kiro(source: Option<String>, credentials_path: &str) -> anyhow::Result<()> {
+    let source = match source {
+        Some(path) => std::path::PathBuf::from(path),
+        None => KiroCredentials::kiro_ide_token_path()
+            .ok_or_else(|| anyhow::anyhow!("无法确定用户目录，请显式指定 Kiro IDE 凭证文件路径"))?,
+    };
+
+    let credentials = KiroCredentials::import_from_kiro_ide(&source)?;
+    let format = CredentialsFormat::from_path(std::path::Path::new(credentials_path));
+    let content = credentials.serialize_as(format)?;
+    write_new_private_file(credentials_path, content.as_bytes()).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::AlreadyExists {
+            anyhow::anyhow!(
+                "凭证文件已存在: {}，请先移除或通过 --credentials 指定其他路径",
+                credentials_path
+            )
+        } else {
+            anyhow::anyhow!("写入凭证文件失败 {}: {}", credentials_path, e)
+        }
+    })?;
+    tracing::info!("已从 {:?} 导入凭证到 {}", source, credentials_path);
+    Ok(())
+}
+
+/// 创建新文件并写入内容，目标已存在时返回 `AlreadyExists`
+///
+/// 文件包含 refreshToken 等敏感信息，unix 下仅所有者可读写（0600）
+fn write_new_private_file(path: &str, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(dir: &std::path::Path, name: &str, content: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_self_check_valid_config() {
+        let dir = std::env::temp_dir().join(format!("kiro-check-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = write_temp(&dir, "config.json", r#"{"apiKey": "sk-check-test-123"}"#);
+        let credentials = write_temp(&dir, "credentials.json", r#"{"refreshToken": "rt"}"#);
+
+        assert_eq!(self_check(&config, &credentials), Ok(()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_self_check_reports_all_problems() {
+        let dir = std::env::temp_dir().join(format!("kiro-check-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = write_temp(&dir, "config.json", r#"{"region": "not a region"}"#);
+        let missing = dir.join("missing.json").to_string_lossy().into_owned();
+
+        let errors = self_check(&config, &missing).unwrap_err();
+        let load_error = KiroCredentials::load(&missing).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "region 格式无效: \"not a region\"（示例: us-east-1）".to_string(),
+                "apiKey 未设置".to_string(),
+                format!("加载凭证失败 {}: {}", missing, load_error),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_new_private_file() {
+        let dir = std::env::temp_dir().join(format!("kiro-import-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("credentials.json").to_string_lossy().into_owned();
+
+        write_new_private_file(&path, b"{}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // 已存在的文件不会被覆盖
+        let err = write_new_private_file(&path, b"overwritten").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -11,4 +11,16 @@ pub struct Args {
     /// 凭证文件路径
     #[arg(long)]
     pub credentials: Option<String>,
+
+    /// 从 Kiro IDE 导入凭证并写入凭证文件后退出
+    ///
+    /// 不指定路径时读取 Kiro IDE 默认位置（~/.aws/sso/cache/kiro-auth-token.json）
+    #[arg(long, value_name = "PATH")]
+    pub import_from_kiro: Option<Option<String>>,
+
+    /// 仅执行启动自检（配置、凭证、Provider 初始化）后退出，不启动服务
+    ///
+    /// 自检通过时退出码为 0，否则输出问题列表并以非零退出码退出
+    #[arg(long)]
+    pub check: bool,
 }
\ No newline at end of file
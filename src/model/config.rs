@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
+use crate::kiro::http_client;
+
 /// KNA 应用配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -24,6 +27,22 @@ pub struct Config {
     #[serde(default)]
     pub api_key: Option<String>,
 
+    /// 从文件读取 apiKey（可选，适用于 Docker/K8s secret 挂载），优先于 `apiKey`
+    #[serde(default)]
+    pub api_key_file: Option<String>,
+
+    /// 管理与调试端点（`/admin/*`、`/debug/*`）使用的独立 API Key（可选，不设置时这些端点不挂载）
+    #[serde(default)]
+    pub admin_api_key: Option<String>,
+
+    /// 从文件读取 adminApiKey（可选），优先于 `adminApiKey`
+    #[serde(default)]
+    pub admin_api_key_file: Option<String>,
+
+    /// 是否挂载管理与调试端点（`/admin/*`、`/debug/*`），关闭后这些路径返回 404
+    #[serde(default = "default_enable_admin_api")]
+    pub enable_admin_api: bool,
+
     #[serde(default = "default_system_version")]
     pub system_version: String,
 
@@ -41,6 +60,116 @@ pub struct Config {
     /// count_tokens API 认证类型（可选，"x-api-key" 或 "bearer"，默认 "x-api-key"）
     #[serde(default = "default_count_tokens_auth_type")]
     pub count_tokens_auth_type: String,
+
+    /// count_tokens API 编码协议（可选，"json" 或 "cbor"，默认 "json"）
+    /// "cbor" 使用 Smithy rpc-v2-cbor 协议，与 Kiro 其他接口一致
+    #[serde(default = "default_count_tokens_protocol")]
+    pub count_tokens_protocol: String,
+
+    /// Token 刷新遇到暂时性错误（网络异常、429、5xx）时的最大重试次数
+    #[serde(default = "default_token_refresh_retries")]
+    pub token_refresh_retries: u32,
+
+    /// Token 刷新首次重试前的等待时间（毫秒），之后每次翻倍
+    #[serde(default = "default_token_refresh_backoff_ms")]
+    pub token_refresh_backoff_ms: u64,
+
+    /// 上游请求建立连接失败（连接被拒绝、DNS 失败等）时的最大重试次数；超时及请求发出后的连接断开不重试
+    #[serde(default = "default_network_retries")]
+    pub network_retries: u32,
+
+    /// 连接错误首次重试前的等待时间（毫秒），之后每次翻倍
+    #[serde(default = "default_network_retry_backoff_ms")]
+    pub network_retry_backoff_ms: u64,
+
+    /// 单个请求的重试时间预算（毫秒，可选），覆盖 Token 刷新重试、限流主动等待、连接错误重试以及区域和模型回退，累计耗时将超过该值时不再等待
+    #[serde(default)]
+    pub request_retry_deadline_ms: Option<u64>,
+
+    /// 时钟偏差告警阈值（秒），本机时间与上游 Date 头相差超过该值时告警
+    #[serde(default = "default_clock_skew_threshold_secs")]
+    pub clock_skew_threshold_secs: u64,
+
+    /// 最大并发流式连接数（可选，不设置则不限制）
+    #[serde(default)]
+    pub max_concurrent_streams: Option<usize>,
+
+    /// 全局请求速率上限（每秒请求数，可选，不设置则不限制）
+    ///
+    /// 设为 0 表示暂时拒绝所有 `/v1` 请求（立即返回 429），与不设置（不限制）不同
+    #[serde(default)]
+    pub global_rate_limit: Option<u32>,
+
+    /// 确定性请求（temperature 为 0 的非流式请求）响应缓存时长（秒，可选，不设置则不缓存）
+    #[serde(default)]
+    pub response_cache_ttl_secs: Option<u64>,
+
+    /// 模型回退链（可选），上游以模型不可用拒绝请求的模型时依次改用备选模型
+    /// 例如 `{"claude-opus-4-5-20251101": ["claude-sonnet-4-5-20250929"]}`
+    #[serde(default)]
+    pub model_fallbacks: HashMap<String, Vec<String>>,
+
+    /// 按模型注入的默认请求参数（可选），只补充客户端未提供的顶层字段
+    /// 例如 `{"claude-opus-4-5-20251101": {"max_tokens": 8192}}`
+    #[serde(default)]
+    pub model_defaults: HashMap<String, serde_json::Value>,
+
+    /// API 错误信息的默认语言（`en` / `zh`），请求可通过 `Accept-Language` 覆盖
+    #[serde(default = "default_error_locale")]
+    pub error_locale: String,
+
+    /// 注入到所有 `/v1/messages` 请求系统提示词最前面的内容（可选）
+    #[serde(default)]
+    pub prepend_system_prompt: Option<String>,
+
+    /// 屏蔽词列表，用户消息文本包含任一屏蔽词（不区分大小写）时拒绝请求
+    #[serde(default)]
+    pub blocked_patterns: Vec<String>,
+
+    /// 上游繁忙时请求的最长排队时间（毫秒，可选，不设置则一直等待），超时返回 503
+    #[serde(default)]
+    pub queue_timeout_ms: Option<u64>,
+
+    /// 客户端通过 `x-kiro-timeout-secs` 请求头指定单次请求超时时允许的上限（秒）
+    #[serde(default = "default_max_request_timeout_secs")]
+    pub max_request_timeout_secs: u64,
+
+    /// 上游接口地址覆盖（可选，用于测试或前置代理），`{region}` 会被替换为区域
+    /// 例如 `http://127.0.0.1:9000/generateAssistantResponse`
+    #[serde(default)]
+    pub upstream_base_override: Option<String>,
+
+    /// 上游 429 比例的滚动统计窗口（秒）
+    #[serde(default = "default_throttle_window_secs")]
+    pub throttle_window_secs: u64,
+
+    /// 上游 429 比例超过该值（0.0 ~ 1.0）时，发送请求前主动等待（可选，不设置则不等待）
+    #[serde(default)]
+    pub throttle_delay_threshold: Option<f64>,
+
+    /// 主动等待的时长（毫秒）
+    #[serde(default = "default_throttle_delay_ms")]
+    pub throttle_delay_ms: u64,
+
+    /// 上游请求只允许 HTTPS（默认关闭）
+    #[serde(default)]
+    pub upstream_https_only: bool,
+
+    /// 上游连接的最低 TLS 版本（可选，"1.0"、"1.1" 或 "1.2"）
+    #[serde(default)]
+    pub upstream_min_tls_version: Option<String>,
+
+    /// 额外信任的根证书文件（可选，PEM 格式，可包含多个证书），用于企业 MITM 代理等场景
+    #[serde(default)]
+    pub upstream_ca_file: Option<String>,
+
+    /// 附加到所有 `/v1` 响应上的自定义响应头（可选），不会覆盖网关自身设置的头
+    #[serde(default)]
+    pub extra_response_headers: HashMap<String, String>,
+
+    /// 认证通过后从入站请求中移除的头（如 Cookie、内部认证信息），确保不会被后续处理或记录
+    #[serde(default = "default_strip_request_headers")]
+    pub strip_request_headers: Vec<String>,
 }
 
 fn default_host() -> String {
@@ -72,6 +201,55 @@ fn default_count_tokens_auth_type() -> String {
     "x-api-key".to_string()
 }
 
+fn default_count_tokens_protocol() -> String {
+    "json".to_string()
+}
+
+fn default_enable_admin_api() -> bool {
+    true
+}
+
+fn default_error_locale() -> String {
+    "zh".to_string()
+}
+
+fn default_token_refresh_retries() -> u32 {
+    2
+}
+
+fn default_token_refresh_backoff_ms() -> u64 {
+    500
+}
+
+fn default_network_retries() -> u32 {
+    1
+}
+
+fn default_network_retry_backoff_ms() -> u64 {
+    200
+}
+
+fn default_clock_skew_threshold_secs() -> u64 {
+    60
+}
+
+/// `maxRequestTimeoutSecs` 的默认值，未从配置构建的 AppState 也使用该值
+pub fn default_max_request_timeout_secs() -> u64 {
+    3600
+}
+
+fn default_throttle_window_secs() -> u64 {
+    60
+}
+
+fn default_throttle_delay_ms() -> u64 {
+    250
+}
+
+fn default_strip_request_headers() -> Vec<String> {
+    vec!["cookie".to_string(), "authorization".to_string()]
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -81,11 +259,41 @@ impl Default for Config {
             kiro_version: default_kiro_version(),
             machine_id: None,
             api_key: None,
+            api_key_file: None,
+            admin_api_key: None,
+            admin_api_key_file: None,
+            enable_admin_api: default_enable_admin_api(),
             system_version: default_system_version(),
             node_version: default_node_version(),
             count_tokens_api_url: None,
             count_tokens_api_key: None,
             count_tokens_auth_type: default_count_tokens_auth_type(),
+            count_tokens_protocol: default_count_tokens_protocol(),
+            token_refresh_retries: default_token_refresh_retries(),
+            token_refresh_backoff_ms: default_token_refresh_backoff_ms(),
+            network_retries: default_network_retries(),
+            network_retry_backoff_ms: default_network_retry_backoff_ms(),
+            request_retry_deadline_ms: None,
+            clock_skew_threshold_secs: default_clock_skew_threshold_secs(),
+            max_concurrent_streams: None,
+            global_rate_limit: None,
+            response_cache_ttl_secs: None,
+            model_fallbacks: HashMap::new(),
+            model_defaults: HashMap::new(),
+            error_locale: default_error_locale(),
+            prepend_system_prompt: None,
+            blocked_patterns: Vec::new(),
+            queue_timeout_ms: None,
+            max_request_timeout_secs: default_max_request_timeout_secs(),
+            upstream_base_override: None,
+            throttle_window_secs: default_throttle_window_secs(),
+            throttle_delay_threshold: None,
+            throttle_delay_ms: default_throttle_delay_ms(),
+            upstream_https_only: false,
+            upstream_min_tls_version: None,
+            upstream_ca_file: None,
+            extra_response_headers: HashMap::new(),
+            strip_request_headers: default_strip_request_headers(),
         }
     }
 }
@@ -105,7 +313,439 @@ impl Config {
         }
 
         let content = fs::read_to_string(path)?;
-        let config: Config = serde_json::from_str(&content)?;
+        let mut config: Config = serde_json::from_str(&content)?;
+        config.load_secret_files()?;
         Ok(config)
     }
+
+    /// 从 `apiKeyFile` / `adminApiKeyFile` 读取密钥
+    ///
+    /// 文件内容去除首尾空白后作为密钥；同时配置了内联值时以文件为准并告警
+    fn load_secret_files(&mut self) -> anyhow::Result<()> {
+        for (name, file, key) in [
+            ("apiKey", &self.api_key_file, &mut self.api_key),
+            ("adminApiKey", &self.admin_api_key_file, &mut self.admin_api_key),
+        ] {
+            let Some(file) = file else {
+                continue;
+            };
+            let secret = fs::read_to_string(file)
+                .map_err(|e| anyhow::anyhow!("读取 {}File 失败 {}: {}", name, file, e))?;
+            if key.is_some() {
+                tracing::warn!("同时配置了 {} 和 {}File，使用文件中的值", name, name);
+            }
+            *key = Some(secret.trim().to_string());
+        }
+        Ok(())
+    }
+
+    /// 校验配置的语义约束
+    ///
+    /// 收集所有问题后一并返回，便于一次性修正
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.host.trim().is_empty() {
+            errors.push("host 不能为空".to_string());
+        }
+        if self.port == 0 {
+            errors.push("port 必须在 1-65535 之间".to_string());
+        }
+        if !is_valid_region(&self.region) {
+            errors.push(format!("region 格式无效: {:?}（示例: us-east-1）", self.region));
+        }
+
+        match self.api_key.as_deref().map(str::trim) {
+            None | Some("") => errors.push("apiKey 未设置".to_string()),
+            Some(key) if key.len() < MIN_API_KEY_LEN => errors.push(format!(
+                "apiKey 过短，至少需要 {} 个字符",
+                MIN_API_KEY_LEN
+            )),
+            Some(_) => {}
+        }
+
+        if let Some(key) = self.admin_api_key.as_deref().map(str::trim)
+            && key.len() < MIN_API_KEY_LEN
+        {
+            errors.push(format!(
+                "adminApiKey 过短，至少需要 {} 个字符",
+                MIN_API_KEY_LEN
+            ));
+        }
+
+        if let Some(machine_id) = &self.machine_id
+            && (machine_id.len() != 64 || !machine_id.chars().all(|c| c.is_ascii_hexdigit()))
+        {
+            errors.push("machineId 必须是 64 位十六进制字符串".to_string());
+        }
+
+        if let Some(url) = &self.count_tokens_api_url
+            && !(url.starts_with("http://") || url.starts_with("https://"))
+        {
+            errors.push(format!("countTokensApiUrl 必须以 http:// 或 https:// 开头: {}", url));
+        }
+        if !matches!(self.count_tokens_auth_type.as_str(), "x-api-key" | "bearer") {
+            errors.push(format!(
+                "countTokensAuthType 只能是 x-api-key 或 bearer: {}",
+                self.count_tokens_auth_type
+            ));
+        }
+        if !matches!(self.count_tokens_protocol.as_str(), "json" | "cbor") {
+            errors.push(format!(
+                "countTokensProtocol 只能是 json 或 cbor: {}",
+                self.count_tokens_protocol
+            ));
+        }
+
+        if let Some(url) = &self.upstream_base_override
+            && !(url.starts_with("http://") || url.starts_with("https://"))
+        {
+            errors.push(format!("upstreamBaseOverride 必须以 http:// 或 https:// 开头: {}", url));
+        }
+
+        if self.clock_skew_threshold_secs == 0 {
+            errors.push("clockSkewThresholdSecs 必须大于 0".to_string());
+        }
+        if self.max_request_timeout_secs == 0 {
+            errors.push("maxRequestTimeoutSecs 必须大于 0".to_string());
+        }
+        if self.max_concurrent_streams == Some(0) {
+            errors.push("maxConcurrentStreams 必须大于 0（不限制请删除该字段）".to_string());
+        }
+
+        for (model, fallbacks) in &self.model_fallbacks {
+            if fallbacks.is_empty() || fallbacks.iter().any(|m| m.trim().is_empty()) {
+                errors.push(format!("modelFallbacks.{} 必须是非空的模型名列表", model));
+            }
+        }
+
+        if self.request_retry_deadline_ms == Some(0) {
+            errors.push("requestRetryDeadlineMs 必须大于 0".to_string());
+        }
+
+        if self.throttle_window_secs == 0 {
+            errors.push("throttleWindowSecs 必须大于 0".to_string());
+        }
+        if let Some(threshold) = self.throttle_delay_threshold
+            && !(0.0..=1.0).contains(&threshold)
+        {
+            errors.push("throttleDelayThreshold 必须在 0.0 到 1.0 之间".to_string());
+        }
+
+        if let Some(version) = &self.upstream_min_tls_version
+            && http_client::parse_tls_version(version).is_none()
+        {
+            let supported: Vec<&str> = http_client::SUPPORTED_TLS_VERSIONS
+                .iter()
+                .map(|(name, _)| *name)
+                .collect();
+            if version == "1.3" {
+                errors.push(format!(
+                    "upstreamMinTlsVersion 不支持 1.3（native-tls 后端无法强制 TLS 1.3），可选: {}",
+                    supported.join("、")
+                ));
+            } else {
+                errors.push(format!(
+                    "upstreamMinTlsVersion 必须是 {} 之一",
+                    supported.join("、")
+                ));
+            }
+        }
+        if self.upstream_https_only
+            && self
+                .upstream_base_override
+                .as_deref()
+                .is_some_and(|url| !url.starts_with("https://"))
+        {
+            errors.push("启用 upstreamHttpsOnly 时 upstreamBaseOverride 必须使用 https://".to_string());
+        }
+
+        for (model, defaults) in &self.model_defaults {
+            if !defaults.is_object() {
+                errors.push(format!("modelDefaults.{} 必须是 JSON 对象", model));
+            }
+        }
+
+        if !SUPPORTED_ERROR_LOCALES.contains(&self.error_locale.as_str()) {
+            errors.push(format!(
+                "errorLocale 必须是 {} 之一",
+                SUPPORTED_ERROR_LOCALES.join("、")
+            ));
+        }
+
+        if self
+            .prepend_system_prompt
+            .as_ref()
+            .is_some_and(|prompt| prompt.trim().is_empty())
+        {
+            errors.push("prependSystemPrompt 不能为空".to_string());
+        }
+        if self.blocked_patterns.iter().any(|p| p.trim().is_empty()) {
+            errors.push("blockedPatterns 不能包含空字符串".to_string());
+        }
+
+        for (name, value) in &self.extra_response_headers {
+            match http::HeaderName::try_from(name.as_str()) {
+                Err(_) => errors.push(format!("extraResponseHeaders 头名称非法: {}", name)),
+                Ok(header) if PROTECTED_RESPONSE_HEADERS.contains(&header.as_str())
+                    || header.as_str().starts_with("access-control-")
+                    || header.as_str().starts_with("x-ratelimit-") =>
+                {
+                    errors.push(format!("extraResponseHeaders 不允许设置受保护的头: {}", name));
+                }
+                Ok(_) => {}
+            }
+            if http::HeaderValue::try_from(value.as_str()).is_err() {
+                errors.push(format!("extraResponseHeaders.{} 的值非法", name));
+            }
+        }
+
+        for name in &self.strip_request_headers {
+            if http::HeaderName::try_from(name.as_str()).is_err() {
+                errors.push(format!("stripRequestHeaders 头名称非法: {}", name));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// 不允许通过 extraResponseHeaders 设置的响应头（由网关或协议本身管理）
+const PROTECTED_RESPONSE_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "transfer-encoding",
+    "connection",
+    "retry-after",
+    "www-authenticate",
+    "set-cookie",
+];
+
+/// `errorLocale` 可选的取值
+const SUPPORTED_ERROR_LOCALES: &[&str] = &["en", "zh"];
+
+/// apiKey 最短长度
+const MIN_API_KEY_LEN: usize = 8;
+
+/// 校验 AWS 区域格式（如 `us-east-1`、`ap-southeast-2`）
+pub fn is_valid_region(region: &str) -> bool {
+    let parts: Vec<&str> = region.split('-').collect();
+    parts.len() >= 3
+        && parts[..parts.len() - 1]
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_lowercase()))
+        && parts[parts.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_digit())
+        && !parts[parts.len() - 1].is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_config() -> Config {
+        Config {
+            api_key: Some("sk-kiro-test-key".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_valid_config() {
+        assert!(valid_config().validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_missing_api_key() {
+        let config = Config::default();
+        assert_eq!(config.validate().unwrap_err(), vec!["apiKey 未设置"]);
+    }
+
+    #[test]
+    fn test_validate_collects_all_errors() {
+        let config = Config {
+            port: 0,
+            region: "".to_string(),
+            api_key: Some("short".to_string()),
+            count_tokens_auth_type: "basic".to_string(),
+            max_concurrent_streams: Some(0),
+            ..valid_config()
+        };
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 5);
+        assert!(errors[0].starts_with("port"));
+        assert!(errors[1].starts_with("region"));
+        assert!(errors[2].starts_with("apiKey 过短"));
+        assert!(errors[3].starts_with("countTokensAuthType"));
+        assert!(errors[4].starts_with("maxConcurrentStreams"));
+    }
+
+    #[test]
+    fn test_validate_machine_id_and_urls() {
+        let config = Config {
+            machine_id: Some("not-hex".to_string()),
+            count_tokens_api_url: Some("ftp://example.com".to_string()),
+            ..valid_config()
+        };
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("machineId"));
+        assert!(errors[1].starts_with("countTokensApiUrl"));
+    }
+
+    #[test]
+    fn test_region_format() {
+        assert!(is_valid_region("us-east-1"));
+        assert!(is_valid_region("ap-southeast-2"));
+        assert!(is_valid_region("us-gov-west-1"));
+        assert!(!is_valid_region("us-east"));
+        assert!(!is_valid_region("US-EAST-1"));
+        assert!(!is_valid_region(""));
+    }
+
+    #[test]
+    fn test_validate_extra_response_headers() {
+        let config = Config {
+            extra_response_headers: HashMap::from([
+                ("x-served-by".to_string(), "edge-1".to_string()),
+                ("bad header".to_string(), "v".to_string()),
+                ("Content-Type".to_string(), "text/plain".to_string()),
+            ]),
+            ..valid_config()
+        };
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.contains("bad header")));
+        assert!(errors.iter().any(|e| e.contains("受保护") && e.contains("Content-Type")));
+    }
+
+    #[test]
+    fn test_strip_request_headers_default_and_validation() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.strip_request_headers, vec!["cookie", "authorization"]);
+
+        let config = Config {
+            strip_request_headers: vec!["x-internal-auth".to_string(), "bad header".to_string()],
+            ..valid_config()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("bad header"));
+    }
+
+    #[test]
+    fn test_load_secret_files_prefers_file() {
+        let dir = std::env::temp_dir().join(format!("kiro-config-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let api_key_file = dir.join("api_key");
+        let admin_key_file = dir.join("admin_api_key");
+        fs::write(&api_key_file, "sk-from-file-123\n").unwrap();
+        fs::write(&admin_key_file, "admin-from-file-456\n").unwrap();
+
+        let mut config = Config {
+            api_key: Some("sk-inline-key".to_string()),
+            api_key_file: Some(api_key_file.to_string_lossy().into_owned()),
+            admin_api_key_file: Some(admin_key_file.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        config.load_secret_files().unwrap();
+
+        assert_eq!(config.api_key.as_deref(), Some("sk-from-file-123"));
+        assert_eq!(config.admin_api_key.as_deref(), Some("admin-from-file-456"));
+
+        config.admin_api_key_file = Some(dir.join("missing").to_string_lossy().into_owned());
+        assert!(config.load_secret_files().is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_upstream_tls_options() {
+        let config = Config {
+            upstream_min_tls_version: Some("1.4".to_string()),
+            upstream_https_only: true,
+            upstream_base_override: Some("http://127.0.0.1:9000/generateAssistantResponse".to_string()),
+            ..valid_config()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.contains("upstreamMinTlsVersion")));
+        assert!(errors.iter().any(|e| e.contains("upstreamHttpsOnly")));
+
+        let config = Config {
+            upstream_min_tls_version: Some("1.3".to_string()),
+            ..valid_config()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("不支持 1.3"));
+    }
+
+    #[test]
+    fn test_validate_throttle_options() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.throttle_window_secs, 60);
+        assert_eq!(config.throttle_delay_threshold, None);
+        assert_eq!(config.throttle_delay_ms, 250);
+
+        let config = Config {
+            throttle_window_secs: 0,
+            throttle_delay_threshold: Some(1.5),
+            ..valid_config()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.contains("throttleWindowSecs")));
+        assert!(errors.iter().any(|e| e.contains("throttleDelayThreshold")));
+    }
+
+    #[test]
+    fn test_validate_request_retry_deadline() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.request_retry_deadline_ms, None);
+
+        let config = Config {
+            request_retry_deadline_ms: Some(0),
+            ..valid_config()
+        };
+        assert_eq!(
+            config.validate().unwrap_err(),
+            vec!["requestRetryDeadlineMs 必须大于 0"]
+        );
+    }
+
+    #[test]
+    fn test_validate_guardrails() {
+        let config = Config {
+            prepend_system_prompt: Some("  ".to_string()),
+            blocked_patterns: vec!["forbidden".to_string(), "".to_string()],
+            ..valid_config()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|e| e.contains("prependSystemPrompt")));
+        assert!(errors.iter().any(|e| e.contains("blockedPatterns")));
+    }
+
+    #[test]
+    fn test_validate_error_locale() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.error_locale, "zh");
+
+        let config = Config {
+            error_locale: "fr".to_string(),
+            ..valid_config()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("errorLocale"));
+    }
 }
@@ -19,6 +19,8 @@ pub struct CountTokensConfig {
     pub api_key: Option<String>,
     /// count_tokens API 认证类型（"x-api-key" 或 "bearer"）
     pub auth_type: String,
+    /// count_tokens API 编码协议（"json" 或 "cbor"）
+    pub protocol: String,
 }
 
 /// 全局配置存储
@@ -158,10 +160,17 @@ async fn call_remote_count_tokens(
         }
     }
 
+    // 按协议编码请求体
+    let (content_type, body) = encode_request(&config.protocol, &request)?;
+    if config.protocol == "cbor" {
+        req_builder = req_builder.header("smithy-protocol", "rpc-v2-cbor");
+    }
+
     // 发送请求
     let response = req_builder
-        .header("Content-Type", "application/json")
-        .json(&request)
+        .header("Content-Type", content_type)
+        .header("Accept", content_type)
+        .body(body)
         .timeout(std::time::Duration::from_secs(5))
         .send()
         .await?;
@@ -170,10 +179,42 @@ async fn call_remote_count_tokens(
         return Err(format!("API 返回错误状态: {}", response.status()).into());
     }
 
-    let result: CountTokensResponse = response.json().await?;
+    let bytes = response.bytes().await?;
+    let result = decode_response(&config.protocol, &bytes)?;
     Ok(result.input_tokens as u64)
 }
 
+/// 按协议编码 count_tokens 请求体
+///
+/// - `"cbor"`：Smithy `rpc-v2-cbor` 协议，Content-Type 为 `application/cbor`
+/// - 其他值：JSON
+///
+/// 返回 (Content-Type, 请求体)
+fn encode_request(
+    protocol: &str,
+    request: &CountTokensRequest,
+) -> Result<(&'static str, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+    if protocol == "cbor" {
+        let mut body = Vec::new();
+        ciborium::into_writer(request, &mut body)?;
+        Ok(("application/cbor", body))
+    } else {
+        Ok(("application/json", serde_json::to_vec(request)?))
+    }
+}
+
+/// 按协议解码 count_tokens 响应体
+fn decode_response(
+    protocol: &str,
+    bytes: &[u8],
+) -> Result<CountTokensResponse, Box<dyn std::error::Error + Send + Sync>> {
+    if protocol == "cbor" {
+        Ok(ciborium::from_reader(bytes)?)
+    } else {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
 /// 本地计算请求的输入 tokens
 fn count_all_tokens_local(system: Option<Vec<SystemMessage>>, messages: Vec<Message>, tools: Option<Vec<Tool>>) -> u64 {
     let mut total = 0;
@@ -232,3 +273,122 @@ pub(crate) fn estimate_output_tokens(content: &[serde_json::Value]) -> i32 {
     total.max(1)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Bytes,
+        http::{HeaderMap, header},
+        response::IntoResponse,
+        routing::post,
+    };
+
+    fn sample_request() -> CountTokensRequest {
+        CountTokensRequest {
+            model: "claude-sonnet-4".to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: serde_json::json!("Hello"),
+            }],
+            system: None,
+            tools: None,
+        }
+    }
+
+    fn test_config(protocol: &str) -> CountTokensConfig {
+        CountTokensConfig {
+            api_url: None,
+            api_key: None,
+            auth_type: "x-api-key".to_string(),
+            protocol: protocol.to_string(),
+        }
+    }
+
+    /// 模拟 count_tokens 服务：按请求的 Content-Type 解码并以相同编码返回固定结果
+    async fn mock_count_tokens(headers: HeaderMap, body: Bytes) -> impl IntoResponse {
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let response = CountTokensResponse { input_tokens: 42 };
+
+        if content_type == "application/cbor" {
+            assert_eq!(headers.get("smithy-protocol").unwrap(), "rpc-v2-cbor");
+            let request: CountTokensRequest = ciborium::from_reader(&body[..]).unwrap();
+            assert_eq!(request.model, "claude-sonnet-4");
+            let mut out = Vec::new();
+            ciborium::into_writer(&response, &mut out).unwrap();
+            ([(header::CONTENT_TYPE, "application/cbor")], out)
+        } else {
+            let request: CountTokensRequest = serde_json::from_slice(&body).unwrap();
+            assert_eq!(request.model, "claude-sonnet-4");
+            (
+                [(header::CONTENT_TYPE, "application/json")],
+                serde_json::to_vec(&response).unwrap(),
+            )
+        }
+    }
+
+    async fn spawn_mock_server() -> String {
+        let app = Router::new().route("/count_tokens", post(mock_count_tokens));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}/count_tokens", addr)
+    }
+
+    #[test]
+    fn test_encode_request_json() {
+        let (content_type, body) = encode_request("json", &sample_request()).unwrap();
+        assert_eq!(content_type, "application/json");
+        let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["model"], "claude-sonnet-4");
+    }
+
+    #[test]
+    fn test_encode_request_cbor() {
+        let (content_type, body) = encode_request("cbor", &sample_request()).unwrap();
+        assert_eq!(content_type, "application/cbor");
+        let decoded: CountTokensRequest = ciborium::from_reader(&body[..]).unwrap();
+        assert_eq!(decoded.model, "claude-sonnet-4");
+        assert_eq!(decoded.messages[0].content, "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_remote_count_tokens_json() {
+        let url = spawn_mock_server().await;
+        let request = sample_request();
+        let tokens = call_remote_count_tokens(
+            &url,
+            &test_config("json"),
+            request.model,
+            &request.system,
+            &request.messages,
+            &request.tools,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tokens, 42);
+    }
+
+    #[tokio::test]
+    async fn test_remote_count_tokens_cbor() {
+        let url = spawn_mock_server().await;
+        let request = sample_request();
+        let tokens = call_remote_count_tokens(
+            &url,
+            &test_config("cbor"),
+            request.model,
+            &request.system,
+            &request.messages,
+            &request.tools,
+        )
+        .await
+        .unwrap();
+        assert_eq!(tokens, 42);
+    }
+}